@@ -1,77 +1,323 @@
-use std::time::Duration;
+//! Shows the notifications of GitHub, GitLab and Gitea accounts, as set up in the
+//! `github-notifications` or `forge-notifications` section. Symlinked as `forge-notifications`, it
+//! works the same.
 
 use itertools::Itertools;
+use notify_rust::Notification;
+use reqwest::Url;
 use serde_json::json;
-use tokio::process::Command;
-use waybar_extensions::waybar::WaybarResponse;
+use tokio::time::Instant;
+use waybar_extensions::config::{Config, Forge, ForgeAccountConfig, GithubNotificationsConfig};
+use waybar_extensions::forge::{
+    create_provider, forge_icon, ForgeNotification, Provider, SeenNotifications,
+};
+use waybar_extensions::waybar::{escape_markup, WaybarResponse};
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// An account on one of the forges together with the state of its notifications
+struct Account {
+    name: String,
+    forge: Forge,
+    provider: Box<dyn Provider>,
+    notifications: Vec<ForgeNotification>,
+    seen: Option<SeenNotifications>,
+    next_poll: Instant,
+    failed: bool,
+}
 
 #[tokio::main]
 async fn main() {
-    use octocrab::Octocrab;
-
-    // let token = std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN env variable is required");
-    let token = String::from_utf8(
-        Command::new("gh")
-            .arg("auth")
-            .arg("token")
-            .output()
-            .await
-            .expect("Could not get Github token")
-            .stdout,
-    )
-    .expect("Github token is not valid UTF-8")
-    .trim_end()
-    .to_owned();
-
-    let octocrab = Octocrab::builder()
-        .personal_token(token)
-        .build()
-        .expect("Could not create octocrab instance");
+    // Falling back to the defaults would quietly drop the configured accounts
+    let config = match Config::load_or_default("waybar", "modules.toml") {
+        Ok(config) => config.github_notifications,
+        Err(e) => {
+            eprintln!("Could not load configuration: {e}");
+            println!(
+                "{}",
+                json!(warning_response(&format!(
+                    "Could not load configuration: {e}"
+                )))
+            );
+            return;
+        }
+    };
+
+    let mut accounts = vec![];
+    let mut errors = vec![];
+    for account_config in config.accounts() {
+        match create_account(&account_config).await {
+            Ok(account) => accounts.push(account),
+            Err(e) => {
+                eprintln!("{e}");
+                errors.push(e.to_string());
+            }
+        }
+    }
+
+    if accounts.is_empty() {
+        println!("{}", json!(warning_response(&errors.join("\n"))));
+        return;
+    }
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => watch(&mut accounts, &config).await,
+        ["mark-read"] => mark_read(&accounts, None).await,
+        ["mark-read", repo] => mark_read(&accounts, Some(repo)).await,
+        ["open-latest"] => open_latest(&mut accounts).await,
+        _ => eprintln!(
+            "Usage: {} [mark-read [OWNER/REPO] | open-latest]",
+            program_name()
+        ),
+    }
+}
+
+/// The name the module was started as, which differs when it runs through a symlink.
+fn program_name() -> String {
+    std::env::args()
+        .next()
+        .as_deref()
+        .map(std::path::Path::new)
+        .and_then(|path| path.file_name())
+        .map_or_else(
+            || String::from("github-notifications"),
+            |name| name.to_string_lossy().into_owned(),
+        )
+}
 
+fn warning_response(message: &str) -> WaybarResponse {
+    WaybarResponse {
+        text: String::from(""),
+        tooltip: escape_markup(message),
+        class: vec![String::from("warning")],
+    }
+}
+
+async fn create_account(config: &ForgeAccountConfig) -> Result<Account, BoxedError> {
+    let provider = create_provider(config).await?;
+
+    // An unnamed github.com account keeps the original file name, so its seen notifications
+    // carry over
+    let state_file = match &config.name {
+        None if config.forge == Forge::Github && config.host == "github.com" => {
+            String::from("github-notifications.json")
+        }
+        _ => format!("github-notifications-{}.json", config.name()),
+    };
+    let seen = SeenNotifications::load("waybar", &state_file)
+        .map_err(|e| eprintln!("Could not load seen notifications: {e}"))
+        .ok();
+
+    Ok(Account {
+        name: config.name().to_owned(),
+        forge: config.forge,
+        provider,
+        notifications: vec![],
+        seen,
+        next_poll: Instant::now(),
+        failed: false,
+    })
+}
+
+async fn watch(accounts: &mut [Account], config: &GithubNotificationsConfig) {
     loop {
-        if let Ok(notifications) = octocrab.activity().notifications().list().send().await {
-            let text = format!("{}", notifications.items.len());
+        for account in accounts
+            .iter_mut()
+            .filter(|account| account.next_poll <= Instant::now())
+        {
+            let result = account.provider.notifications().await;
+            account.next_poll = Instant::now() + account.provider.next_delay();
+            account.failed = result.is_err();
+
+            let Ok(notifications) = result else {
+                continue;
+            };
+            account.notifications = notifications;
+
+            if let Some(seen) = &mut account.seen {
+                for notification in seen
+                    .update(&account.notifications)
+                    .into_iter()
+                    .filter(|notification| config.notify_reasons.contains(&notification.reason))
+                {
+                    show_notification(notification, account.provider.html_url(notification).await);
+                }
+
+                if let Err(e) = seen.save() {
+                    eprintln!("Could not store seen notifications: {e}");
+                }
+            }
+        }
+
+        println!("{}", json!(waybar_response(accounts)));
+
+        if let Some(next_poll) = accounts.iter().map(|account| account.next_poll).min() {
+            tokio::time::sleep_until(next_poll).await;
+        }
+    }
+}
+
+fn waybar_response(accounts: &[Account]) -> WaybarResponse {
+    if accounts.iter().all(|account| account.failed) {
+        return WaybarResponse {
+            text: String::from(""),
+            tooltip: String::from("Could not request notifications"),
+            class: vec![String::from("warning")],
+        };
+    }
+
+    let count: usize = accounts
+        .iter()
+        .map(|account| account.notifications.len())
+        .sum();
 
-            let tooltip = notifications
-                .items
+    let tooltip = accounts
+        .iter()
+        .map(|account| {
+            let notifications = account
+                .notifications
                 .iter()
                 .map(|notification| {
                     format!(
                         "{}  {}",
-                        get_icon(&notification.subject.r#type),
-                        notification.subject.title
+                        get_icon(&notification.kind),
+                        escape_markup(&notification.title)
                     )
                 })
                 .join("\n");
 
-            let mut class = vec![];
+            let notifications = if account.failed {
+                format!("Could not request notifications\n{notifications}")
+            } else {
+                notifications
+            };
 
-            if !notifications.items.is_empty() {
-                class.push(String::from("active"));
+            if accounts.len() > 1 {
+                format!(
+                    "<b>{}  {}</b>\n{notifications}",
+                    forge_icon(account.forge),
+                    escape_markup(&account.name)
+                )
             } else {
-                class.push(String::from("passive"));
+                notifications
             }
+        })
+        .join("\n\n");
 
-            println!(
-                "{}",
-                json!(WaybarResponse {
-                    text,
-                    tooltip,
-                    class,
-                })
-            );
-        } else {
-            println!(
-                "{}",
-                json!(WaybarResponse {
-                    text: String::from(""),
-                    tooltip: String::from("Could not request notifications"),
-                    class: vec![String::from("warning")],
-                })
-            );
+    let mut class = vec![];
+
+    if count > 0 {
+        class.push(String::from("active"));
+    } else {
+        class.push(String::from("passive"));
+    }
+
+    if accounts.iter().any(|account| account.failed) {
+        class.push(String::from("warning"));
+    }
+
+    WaybarResponse {
+        text: format!("{count}"),
+        tooltip,
+        class,
+    }
+}
+
+/// Marks notifications as read on every account. A repository only exists on one of the hosts,
+/// so marking it only fails when no account knows it.
+async fn mark_read(accounts: &[Account], repo: Option<&str>) {
+    let repo = match repo.map(|repo| repo.split_once('/')) {
+        None => None,
+        Some(Some(repo)) => Some(repo),
+        Some(None) => {
+            eprintln!("Repository should be formatted as OWNER/REPO");
+            return;
+        }
+    };
+
+    let mut succeeded = false;
+    for account in accounts {
+        match account.provider.mark_read(repo).await {
+            Ok(()) => succeeded = true,
+            Err(_) if repo.is_some() => {}
+            Err(_) => eprintln!("Could not mark notifications as read for {}", account.name),
         }
+    }
+
+    if repo.is_some() && !succeeded {
+        eprintln!("Could not mark notifications as read");
+    }
+}
+
+/// Opens the most recently updated notification of all accounts. The providers read every page,
+/// so this does not depend on the order the forges sort their notifications in.
+async fn open_latest(accounts: &mut [Account]) {
+    let mut latest = None;
+    for account in accounts.iter_mut() {
+        match account.provider.notifications().await {
+            Ok(notifications) => {
+                latest = notifications
+                    .into_iter()
+                    .map(|notification| (notification, &*account.provider))
+                    .chain(latest)
+                    .max_by_key(|(notification, _)| notification.updated_at)
+            }
+            Err(_) => eprintln!("Could not request notifications for {}", account.name),
+        }
+    }
 
-        tokio::time::sleep(Duration::from_secs(300)).await;
+    let Some((notification, provider)) = latest else {
+        return;
+    };
+
+    match provider.html_url(&notification).await {
+        Some(url) => open_url(url.as_str()),
+        None => eprintln!("Could not find a page for the latest notification"),
+    }
+}
+
+fn open_url(url: &str) {
+    if std::process::Command::new("xdg-open")
+        .arg(url)
+        .spawn()
+        .is_err()
+    {
+        eprintln!("Could not open {url}");
+    }
+}
+
+fn show_notification(notification: &ForgeNotification, url: Option<Url>) {
+    let mut desktop_notification = Notification::new();
+    desktop_notification
+        .summary(&notification.repository)
+        .body(&format!(
+            "{}  {}",
+            get_icon(&notification.kind),
+            notification.title
+        ));
+    if url.is_some() {
+        desktop_notification.action("default", "Open");
+    }
+
+    match desktop_notification.show() {
+        Ok(handle) => {
+            if let Some(url) = url {
+                tokio::task::spawn_blocking(move || {
+                    handle.wait_for_action(|action| {
+                        if action == "default" {
+                            open_url(url.as_str());
+                        }
+                    })
+                });
+            }
+        }
+        Err(_) => eprintln!("Could not send notification"),
     }
 }
 
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use octocrab::Octocrab;
+use serde_json::json;
+use waybar_extensions::config::{Config, GithubReviewQueueConfig};
+use waybar_extensions::forge::token;
+use waybar_extensions::github::create_graphql_octocrab;
+use waybar_extensions::github::pull_requests::{search, viewer, CiState, PullRequest};
+use waybar_extensions::waybar::{columnize_output, escape_markup, WaybarResponse};
+
+#[tokio::main]
+async fn main() {
+    let config = match Config::load_or_default("waybar", "modules.toml") {
+        Ok(config) => config.github_review_queue,
+        Err(e) => {
+            eprintln!("Could not load configuration: {e}");
+            println!(
+                "{}",
+                json!(WaybarResponse {
+                    text: String::from(""),
+                    tooltip: escape_markup(&format!("Could not load configuration: {e}")),
+                    class: vec![String::from("warning")],
+                })
+            );
+            return;
+        }
+    };
+
+    let Some(octocrab) = token::resolve(&config.account)
+        .await
+        .and_then(|token| create_graphql_octocrab(&config.account, token))
+    else {
+        println!(
+            "{}",
+            json!(WaybarResponse {
+                text: String::from(""),
+                tooltip: format!(
+                    "Could not find a GitHub token, {}",
+                    token::missing_token_hint(&config.account)
+                ),
+                class: vec![String::from("warning")],
+            })
+        );
+        return;
+    };
+
+    loop {
+        if let Ok((review_requests, authored)) = search_all(&octocrab, &config).await {
+            println!(
+                "{}",
+                json!(waybar_response(&config, &review_requests, &authored))
+            );
+        } else {
+            println!(
+                "{}",
+                json!(WaybarResponse {
+                    text: String::from(""),
+                    tooltip: String::from("Could not search pull requests"),
+                    class: vec![String::from("warning")],
+                })
+            );
+        }
+
+        tokio::time::sleep(Duration::from_secs(config.interval)).await;
+    }
+}
+
+/// Runs the review and authored searches. The viewer is looked up every time, so joining or
+/// leaving a team shows up.
+async fn search_all(
+    octocrab: &Octocrab,
+    config: &GithubReviewQueueConfig,
+) -> Result<(Vec<PullRequest>, Vec<PullRequest>), octocrab::Error> {
+    let viewer = viewer(octocrab).await?;
+
+    Ok((
+        search(octocrab, &config.review_query, &viewer).await?,
+        search(octocrab, &config.authored_query, &viewer).await?,
+    ))
+}
+
+fn waybar_response(
+    config: &GithubReviewQueueConfig,
+    review_requests: &[PullRequest],
+    authored: &[PullRequest],
+) -> WaybarResponse {
+    let failing = authored
+        .iter()
+        .filter(|pull_request| pull_request.ci_state.is_failing())
+        .count();
+
+    let text = format!("  {}    {failing}", review_requests.len());
+
+    let headings = [
+        String::from("Repository"),
+        String::from("Pull request"),
+        String::from("CI"),
+        String::from("Age"),
+    ];
+    let tooltip = format!(
+        "Waiting for your review\n{}\n\nYour pull requests\n{}",
+        columnize_output(&pull_request_rows(review_requests), &headings),
+        columnize_output(&pull_request_rows(authored), &headings)
+    );
+
+    // A review counts as waiting from the moment it was requested, not since the pull request
+    // was opened
+    let oldest_review_request = review_requests
+        .iter()
+        .map(|pull_request| {
+            Utc::now()
+                - pull_request
+                    .review_requested_at
+                    .unwrap_or(pull_request.created_at)
+        })
+        .max();
+
+    let mut class = vec![];
+
+    if !review_requests.is_empty() {
+        class.push(String::from("active"));
+    } else {
+        class.push(String::from("passive"));
+    }
+
+    if oldest_review_request.is_some_and(|age| age.num_hours() >= config.review_warning_age as i64)
+    {
+        class.push(String::from("warning"));
+    }
+
+    if failing > 0 {
+        class.push(String::from("failing"));
+    }
+
+    WaybarResponse {
+        text,
+        tooltip,
+        class,
+    }
+}
+
+fn pull_request_rows(pull_requests: &[PullRequest]) -> Vec<Vec<String>> {
+    pull_requests
+        .iter()
+        .map(|pull_request| {
+            vec![
+                pull_request.repository.clone(),
+                pull_request.title.clone(),
+                get_ci_icon(pull_request.ci_state).to_owned(),
+                format_age(Utc::now() - pull_request.created_at),
+            ]
+        })
+        .collect()
+}
+
+fn format_age(age: chrono::Duration) -> String {
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}m", age.num_minutes())
+    }
+}
+
+fn get_ci_icon(ci_state: CiState) -> &'static str {
+    match ci_state {
+        CiState::Success => "",
+        CiState::Pending | CiState::Expected => "",
+        CiState::Failure | CiState::Error => "",
+        CiState::None => "-",
+    }
+}
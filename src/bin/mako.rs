@@ -1,63 +1,71 @@
 use std::error::Error;
-use std::process::Command;
+use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use waybar_extensions::config::{Config, MakoConfig};
+use waybar_extensions::mako::{invoke, Mako};
+use waybar_extensions::notifications::{watch_bus, waybar_response, NotificationDaemon};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct MakoNotificationField<T> {
-    data: T,
-}
+/// Interval between calls to `makoctl` when watching without D-Bus
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-#[derive(Serialize, Deserialize, Debug)]
-struct MakoNotification {
-    #[serde(rename = "app-name")]
-    app_name: MakoNotificationField<String>,
-    #[serde(rename = "app-icon")]
-    app_icon: MakoNotificationField<String>,
-    category: MakoNotificationField<String>,
-    summary: MakoNotificationField<String>,
-    body: MakoNotificationField<String>,
-    id: MakoNotificationField<u32>,
-    actions: MakoNotificationField<Value>,
-}
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::load_or_default("waybar", "modules.toml")
+        .map(|config| config.mako)
+        .unwrap_or_else(|e| {
+            eprintln!("Could not load configuration, using defaults: {e}");
+            MakoConfig::default()
+        });
+    let mako = Mako {
+        dnd_mode: config.dnd_mode.clone(),
+    };
 
-#[derive(Deserialize, Debug)]
-struct MakoctlResponse {
-    data: Vec<Vec<MakoNotification>>,
-}
+    match std::env::args().nth(1).as_deref() {
+        None => println!("{}", format_output(&config, &mako)?),
+        Some("watch") => watch(&config, &mako),
+        Some("toggle-dnd") => mako.toggle_pause()?,
+        Some("restore") => mako.restore()?,
+        Some("dismiss-all") => mako.dismiss_all()?,
+        Some("invoke") => {
+            let id = std::env::args()
+                .nth(2)
+                .ok_or("Missing notification id")?
+                .parse()?;
+            invoke(id)?
+        }
+        Some(_) => {
+            eprintln!("Usage: mako [watch | toggle-dnd | restore | dismiss-all | invoke ID]")
+        }
+    }
 
-#[derive(Serialize)]
-struct WaybarResponse {
-    text: String,
-    tooltip: String,
-    class: Vec<String>,
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let command_output = Command::new("makoctl").arg("list").output()?;
-    let response: MakoctlResponse = serde_json::from_slice(&command_output.stdout)?;
-
-    let notifications = response
-        .data
-        .into_iter()
-        .next()
-        .expect("Data format has changed");
-
-    let waybar_response = WaybarResponse {
-        text: notifications.len().to_string(),
-        tooltip: notifications
-            .iter()
-            .map(|y| y.summary.data.clone())
-            .collect::<Vec<_>>()
-            .join("\n"),
-        class: vec![],
+/// Prints a line every time the notifications or modes change, using D-Bus when possible and
+/// polling `makoctl` otherwise.
+fn watch(config: &MakoConfig, mako: &Mako) {
+    let mut last_output = None;
+    let mut print_changes = || match format_output(config, mako) {
+        Ok(output) => {
+            if last_output.as_ref() != Some(&output) {
+                println!("{output}");
+                last_output = Some(output);
+            }
+        }
+        Err(_) => eprintln!("Could not list notifications"),
     };
 
-    let output =
-        serde_json::to_string(&waybar_response).expect("Cannot create output from notifications");
+    print_changes();
 
-    println!("{}", output);
+    if watch_bus(&mut print_changes).is_err() {
+        eprintln!("Lost connection to D-Bus, falling back to makoctl");
+    }
 
-    Ok(())
+    loop {
+        print_changes();
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn format_output(config: &MakoConfig, mako: &Mako) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string(&waybar_response(config, mako)?)?)
 }
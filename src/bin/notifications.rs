@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::time::Duration;
+
+use waybar_extensions::config::{Config, NotificationsConfig};
+use waybar_extensions::notifications::{
+    detect, watch_bus, waybar_response, NotificationDaemon, WaybarResponse,
+};
+use waybar_extensions::waybar::escape_markup;
+
+/// Interval between updates when the session bus cannot be monitored, and between attempts to
+/// find the daemon
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::load_or_default("waybar", "modules.toml")
+        .map(|config| config.notifications)
+        .unwrap_or_else(|e| {
+            eprintln!("Could not load configuration, using defaults: {e}");
+            NotificationsConfig::default()
+        });
+
+    match std::env::args().nth(1).as_deref() {
+        None => match detect(&config) {
+            Ok(daemon) => println!("{}", format_output(&config, daemon.as_ref())?),
+            Err(error) => println!("{}", unavailable_output(&error.to_string())?),
+        },
+        Some("watch") => watch(&config, wait_for_daemon(&config).as_ref()),
+        Some("toggle-pause") => detect(&config)?.toggle_pause()?,
+        Some("restore") => detect(&config)?.restore()?,
+        Some("dismiss-all") => detect(&config)?.dismiss_all()?,
+        Some(_) => eprintln!("Usage: notifications [watch | toggle-pause | restore | dismiss-all]"),
+    }
+
+    Ok(())
+}
+
+/// Tries to find the daemon until it shows up, as the bar often starts before the notification
+/// daemon does. Shows a warning in the meantime.
+fn wait_for_daemon(config: &NotificationsConfig) -> Box<dyn NotificationDaemon> {
+    let mut last_error = None;
+
+    loop {
+        match detect(config) {
+            Ok(daemon) => return daemon,
+            Err(error) => {
+                let error = error.to_string();
+                if last_error.as_ref() != Some(&error) {
+                    eprintln!("Could not find the notification daemon: {error}");
+                    match unavailable_output(&error) {
+                        Ok(output) => println!("{output}"),
+                        Err(_) => eprintln!("Could not format waybar response"),
+                    }
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn unavailable_output(error: &str) -> Result<String, Box<dyn Error>> {
+    let waybar_response = WaybarResponse {
+        text: String::new(),
+        tooltip: escape_markup(&format!("No notification daemon found: {error}")),
+        alt: String::from("default"),
+        class: vec![String::from("warning")],
+    };
+
+    Ok(serde_json::to_string(&waybar_response)?)
+}
+
+/// Prints a line every time the state of the daemon changes, checking on every call to the
+/// daemon seen on the session bus, or polling when the bus cannot be monitored.
+fn watch(config: &NotificationsConfig, daemon: &dyn NotificationDaemon) {
+    let mut last_output = None;
+    let mut print_changes = || match format_output(config, daemon) {
+        Ok(output) => {
+            if last_output.as_ref() != Some(&output) {
+                println!("{output}");
+                last_output = Some(output);
+            }
+        }
+        Err(_) => eprintln!("Could not get notifications"),
+    };
+
+    print_changes();
+
+    if watch_bus(&mut print_changes).is_err() {
+        eprintln!("Could not monitor D-Bus, falling back to polling");
+    }
+
+    loop {
+        print_changes();
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn format_output(
+    config: &NotificationsConfig,
+    daemon: &dyn NotificationDaemon,
+) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string(&waybar_response(
+        &config.mako,
+        daemon,
+    )?)?)
+}
@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 use std::process::Command;
 
+use waybar_extensions::systemd::{self, Manager};
+use waybar_extensions::waybar::columnize_output;
+
 fn get_current_kernel() -> Result<String, Box<dyn std::error::Error>> {
     let command_output = Command::new("uname").arg("-r").output()?;
     let mut output_string = String::from_utf8(command_output.stdout)?;
@@ -23,41 +26,54 @@ fn loaded_kernel_has_modules_installed() -> Result<bool, Box<dyn std::error::Err
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let outdated_kernel = !loaded_kernel_has_modules_installed()?;
 
-    let command_output = Command::new("systemctl")
-        .arg("--user")
-        .arg("list-units")
-        .arg("--failed")
-        .output()?;
-
-    let output_string = String::from_utf8(command_output.stdout)?;
-
-    let failed_units = output_string
-        .split('\n')
-        .skip(1)
-        .take_while(|&row| !row.is_empty())
-        .filter_map(|row| {
-            if row.split_ascii_whitespace().nth(3)? == "failed" {
-                return row.split_ascii_whitespace().nth(1);
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    let mut failed_units = vec![];
+    for manager in [Manager::User, Manager::System] {
+        match systemd::failed_units(manager) {
+            Ok(units) => failed_units.extend(units),
+            Err(e) => eprintln!("Could not list failed {} units: {e}", manager.name()),
+        }
+    }
 
     let warning_count = failed_units.len() + if outdated_kernel { 1 } else { 0 };
 
-    let class = if !failed_units.is_empty() {
-        vec!["critical".to_owned()]
+    let mut class = vec![];
+    if !failed_units.is_empty() {
+        class.push("critical".to_owned());
     } else if outdated_kernel {
-        vec!["warning".to_owned()]
-    } else {
-        vec![]
-    };
+        class.push("warning".to_owned());
+    }
+    for manager in [Manager::User, Manager::System] {
+        if failed_units.iter().any(|unit| unit.manager == manager) {
+            class.push(format!("{}-failed", manager.name()));
+        }
+    }
 
-    let mut tooltip: String = failed_units.join("\n");
+    let mut tooltip = String::new();
+
+    if !failed_units.is_empty() {
+        let output = failed_units
+            .iter()
+            .map(|unit| {
+                vec![
+                    unit.manager.name().to_owned(),
+                    unit.name.clone(),
+                    unit.description.clone(),
+                    unit.failed_at
+                        .map(|failed_at| failed_at.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let headings = ["Manager", "Unit", "Description", "Failed since"].map(String::from);
+
+        tooltip.push_str(&columnize_output(&output, &headings));
+    }
 
     if outdated_kernel {
-        tooltip.push_str("\nLoaded kernel is outdated");
+        if !tooltip.is_empty() {
+            tooltip.push('\n');
+        }
+        tooltip.push_str("Loaded kernel is outdated");
     }
 
     let waybar_response = waybar_extensions::waybar::WaybarResponse {
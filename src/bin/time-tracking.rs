@@ -0,0 +1,681 @@
+//! Tracks the time spent on tasks. Symlinked as `org-clock`, the former name of the module, it
+//! works the same.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime};
+use notify_rust::Notification;
+use serde::Serialize;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+use waybar_extensions::clock::{
+    create_backend, Clock, ClockBackend, ClockMachine, Event, Phase, SystemTime, TimeSource,
+};
+use waybar_extensions::config::{Config, TimeTrackingConfig};
+use waybar_extensions::logind::Session;
+use waybar_extensions::org::{clocked_time, ClockEntry};
+use waybar_extensions::waybar::columnize_output;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// How often to ask the backend for the running clock
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often to read the agenda files again for the totals
+const TOTALS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often to ask logind whether the session is idle
+const IDLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long "Snooze" on a break notification puts off the next one
+const SNOOZE_TIME: Duration = Duration::minutes(10);
+
+const DEFAULT_MENU_COMMAND: &str = "rofi -dmenu -i -p 'Clock in'";
+
+#[derive(Serialize)]
+struct WaybarResponse {
+    text: String,
+    tooltip: String,
+    class: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<i64>,
+}
+
+/// A choice made in a notification, which arrives while the module keeps running
+enum Response {
+    ClockOut,
+    Snooze,
+    DiscardIdle(DateTime<Local>),
+    ClockOutAt(DateTime<Local>),
+}
+
+/// The name the module was started as, which differs when it runs through a symlink.
+fn program_name() -> String {
+    std::env::args()
+        .next()
+        .as_deref()
+        .map(std::path::Path::new)
+        .and_then(|path| path.file_name())
+        .map_or_else(
+            || String::from("time-tracking"),
+            |name| name.to_string_lossy().into_owned(),
+        )
+}
+
+#[tokio::main]
+async fn main() {
+    let config = Config::load_or_default("waybar", "modules.toml")
+        .unwrap_or_else(|e| {
+            eprintln!("Could not load configuration, using defaults: {e}");
+            Config::default()
+        })
+        .time_tracking;
+    let backend = match create_backend(config.as_ref()) {
+        Ok(backend) => backend,
+        Err(error) => {
+            eprintln!("Could not set up time tracking: {error}");
+            return;
+        }
+    };
+    let backend = backend.as_ref();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => {
+            watch(backend, &SystemTime, config.as_ref()).await;
+            Ok(())
+        }
+        ["clock-out"] => backend.clock_out(None).await,
+        ["clock-in-last"] => backend.clock_in_last().await,
+        ["clock-in", task] => backend.clock_in(task).await,
+        ["recent"] => backend.recent_tasks().await.map(|tasks| {
+            for task in tasks {
+                println!("{task}");
+            }
+        }),
+        ["switch"] => switch_task(backend, config.as_ref()).await,
+        _ => {
+            eprintln!(
+                "Usage: {} [clock-out | clock-in-last | clock-in TASK | recent | switch]",
+                program_name()
+            );
+            Ok(())
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("{error}");
+    }
+}
+
+/// Offers the recent tasks in a menu like rofi or fuzzel and clocks into the selected one.
+async fn switch_task(
+    backend: &dyn ClockBackend,
+    config: Option<&TimeTrackingConfig>,
+) -> Result<(), BoxedError> {
+    let tasks = backend.recent_tasks().await?;
+    let menu_command = config
+        .and_then(|config| config.menu_command.as_deref())
+        .unwrap_or(DEFAULT_MENU_COMMAND);
+
+    let mut menu = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(menu_command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    menu.stdin
+        .take()
+        .ok_or("Could not write to the menu")?
+        .write_all(tasks.join("\n").as_bytes())
+        .await?;
+
+    let output = menu.wait_with_output().await?;
+    let selection = String::from_utf8(output.stdout)?;
+    let selection = selection.trim_end_matches('\n');
+
+    // Closing the menu without choosing is not an error
+    if selection.is_empty() {
+        return Ok(());
+    }
+
+    backend.clock_in(selection).await
+}
+
+/// Prints the state every second, asking the backend for the clock in between and acting on
+/// whatever the clock machine reports.
+async fn watch(
+    backend: &dyn ClockBackend,
+    time: &dyn TimeSource,
+    config: Option<&TimeTrackingConfig>,
+) {
+    if config.is_none() {
+        eprintln!("Could not load configuration, will not be showing notifications");
+    }
+
+    let mut machine = ClockMachine::new(config, time.now());
+
+    let session = match config.and_then(|config| config.idle_time) {
+        Some(_) => Session::connect()
+            .await
+            .map_err(|_| eprintln!("Could not connect to logind, will not be detecting idle time"))
+            .ok(),
+        None => None,
+    };
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut last_poll = None;
+    let mut last_entries_read = None;
+    let mut last_idle_check = None;
+
+    loop {
+        let now = time.now();
+
+        if is_due(last_poll, POLL_INTERVAL) {
+            match backend.clock().await {
+                Ok(clock) => machine.set_clock(clock, now),
+                Err(_) => eprintln!("Something went wrong when checking clock"),
+            }
+            last_poll = Some(Instant::now());
+        }
+
+        if is_due(last_entries_read, TOTALS_INTERVAL) {
+            match backend.entries(week_start(now.naive_local())).await {
+                Ok(entries) => machine.set_entries(entries),
+                Err(_) => eprintln!("Could not read clocked time"),
+            }
+            last_entries_read = Some(Instant::now());
+        }
+
+        let mut events = vec![];
+        if let Some(session) = session
+            .as_ref()
+            .filter(|_| is_due(last_idle_check, IDLE_INTERVAL))
+        {
+            match session.idle_since().await {
+                Ok(since) => events.extend(machine.set_idle(since.map(DateTime::from), now)),
+                Err(_) => eprintln!("Could not ask logind whether the session is idle"),
+            }
+            last_idle_check = Some(Instant::now());
+        }
+        events.append(&mut machine.tick(now));
+
+        for event in events {
+            if handle_event(event, backend, config, &sender).await {
+                last_poll = None;
+            }
+        }
+
+        if let Ok(result) = serde_json::to_string(&waybar_response(&machine, config, now)) {
+            println!("{result}");
+        } else {
+            eprintln!("Could not format waybar response");
+        }
+
+        let wait_duration = 1_000_000_000u32 - time.now().timestamp_subsec_nanos();
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_nanos(wait_duration.into())) => {}
+            Some(response) = receiver.recv() => {
+                if handle_response(response, backend, &mut machine, time.now()).await {
+                    last_poll = None;
+                }
+            }
+        }
+    }
+}
+
+fn is_due(last: Option<Instant>, interval: std::time::Duration) -> bool {
+    last.is_none_or(|last| last.elapsed() >= interval)
+}
+
+/// Sends the notification for an event. Returns whether the clock was changed.
+async fn handle_event(
+    event: Event,
+    backend: &dyn ClockBackend,
+    config: Option<&TimeTrackingConfig>,
+    sender: &UnboundedSender<Response>,
+) -> bool {
+    match event {
+        Event::BreakDue { worked } => {
+            ask(
+                Notification::new()
+                    .summary("Time for a break")
+                    .body(&format!(
+                        "You've worked for {} minutes",
+                        worked.num_minutes()
+                    ))
+                    .action("clock-out", "Clock out")
+                    .action(
+                        "snooze",
+                        &format!("Snooze {} min", SNOOZE_TIME.num_minutes()),
+                    )
+                    .action("dismiss", "Dismiss"),
+                sender.clone(),
+                |action| match action {
+                    "clock-out" => Some(Response::ClockOut),
+                    "snooze" => Some(Response::Snooze),
+                    _ => None,
+                },
+            );
+            false
+        }
+        Event::BreakStarted { phase, length } => {
+            let summary = if phase == Phase::LongBreak {
+                "Time for a long break"
+            } else {
+                "Time for a short break"
+            };
+            notify(
+                summary,
+                &format!("Take {} minutes off", length.num_minutes()),
+            );
+
+            if config.is_some_and(|config| config.pomodoro.clock_out) {
+                if backend.clock_out(None).await.is_err() {
+                    eprintln!("Could not clock out for the break");
+                }
+                return true;
+            }
+            false
+        }
+        Event::BreakOver { clocked_in: true } => {
+            notify("Break is over", "Back to work");
+            false
+        }
+        Event::BreakOver { clocked_in: false } => {
+            notify("Break is over", "Clock in to start the next pomodoro");
+            false
+        }
+        Event::Untracked { untracked } => {
+            notify(
+                "No clock is running",
+                &format!(
+                    "You have not tracked the last {} minutes, clock in to keep track",
+                    untracked.num_minutes()
+                ),
+            );
+            false
+        }
+        Event::IdleReturned { since } => {
+            prompt_idle_time(since, sender.clone());
+            false
+        }
+    }
+}
+
+fn notify(summary: &str, body: &str) {
+    if Notification::new()
+        .summary(summary)
+        .body(body)
+        .show()
+        .is_err()
+    {
+        eprintln!("Could not send notification");
+    }
+}
+
+/// Asks what to do with the time spent away.
+fn prompt_idle_time(since: DateTime<Local>, sender: UnboundedSender<Response>) {
+    ask(
+        Notification::new()
+            .summary("Welcome back")
+            .body(&format!(
+                "You have been away since {}, the clock kept running",
+                since.format("%H:%M")
+            ))
+            .action("discard", "Discard idle time")
+            .action(
+                "clock-out",
+                &format!("Clock out at {}", since.format("%H:%M")),
+            )
+            .action("keep", "Keep"),
+        sender,
+        move |action| match action {
+            "discard" => Some(Response::DiscardIdle(since)),
+            "clock-out" => Some(Response::ClockOutAt(since)),
+            _ => None,
+        },
+    );
+}
+
+/// Shows a notification with actions and sends the response to the chosen one back to the
+/// module, without holding it up while the notification is open.
+fn ask(
+    notification: &mut Notification,
+    sender: UnboundedSender<Response>,
+    respond: impl FnOnce(&str) -> Option<Response> + Send + 'static,
+) {
+    let Ok(handle) = notification.show() else {
+        eprintln!("Could not send notification");
+        return;
+    };
+
+    tokio::task::spawn_blocking(move || {
+        handle.wait_for_action(|action| {
+            if let Some(response) = respond(action) {
+                let _ = sender.send(response);
+            }
+        })
+    });
+}
+
+/// Acts on a choice made in a notification. Returns whether the clock was changed.
+async fn handle_response(
+    response: Response,
+    backend: &dyn ClockBackend,
+    machine: &mut ClockMachine<'_>,
+    now: DateTime<Local>,
+) -> bool {
+    let (result, error) = match response {
+        Response::Snooze => {
+            machine.snooze(now + SNOOZE_TIME);
+            return false;
+        }
+        Response::ClockOut => (backend.clock_out(None).await, "Could not clock out"),
+        // The clock stopped or changed since the notification, discarding would clock in again
+        Response::DiscardIdle(since) if machine.clock().is_none_or(|clock| clock.start > since) => {
+            return false;
+        }
+        Response::DiscardIdle(since) => (
+            backend.discard_since(since).await,
+            "Could not discard idle time",
+        ),
+        Response::ClockOutAt(since) => (
+            backend.clock_out(Some(since)).await,
+            "Could not discard idle time",
+        ),
+    };
+
+    if result.is_err() {
+        eprintln!("{error}");
+    }
+    true
+}
+
+fn waybar_response(
+    machine: &ClockMachine,
+    config: Option<&TimeTrackingConfig>,
+    now: DateTime<Local>,
+) -> WaybarResponse {
+    let pomodoro = machine.pomodoro();
+    let effort_progress = machine.effort_progress(now);
+
+    let text = match (pomodoro, machine.clock(), effort_progress) {
+        (Some((pomodoro, pomodoro_config)), _, _) if pomodoro.is_break() => format!(
+            "Break: {}",
+            format_countdown(pomodoro.remaining(pomodoro_config, now))
+        ),
+        (Some((pomodoro, pomodoro_config)), Some(clock), _) if pomodoro.phase == Phase::Work => {
+            format!(
+                "{}: {}",
+                clock.task,
+                format_countdown(pomodoro.remaining(pomodoro_config, now))
+            )
+        }
+        (_, Some(clock), Some((clocked, effort))) => {
+            format!(
+                "{}: {}/{}",
+                clock.task,
+                format_duration(clocked),
+                format_duration(effort)
+            )
+        }
+        (_, Some(clock), None) => {
+            let duration = machine.elapsed(now).unwrap_or_else(Duration::zero);
+            let hours = duration.num_hours();
+            let minutes = duration.num_minutes() % 60;
+            let seconds = duration.num_seconds() % 60;
+            format!("{}: {hours:02}:{minutes:02}:{seconds:02}", clock.task)
+        }
+        (_, None, _) => "Untracked time".to_string(),
+    };
+
+    let (tooltip, percentage) = totals(
+        machine.entries(),
+        machine.clock(),
+        config,
+        now.naive_local(),
+    );
+
+    // The progress on the current task is more telling than the one of the day
+    let (tooltip, percentage) = match effort_progress {
+        Some((clocked, effort)) => (
+            format!(
+                "Effort: {} of {}\n{tooltip}",
+                format_duration(clocked),
+                format_duration(effort)
+            ),
+            Some((clocked.num_minutes() * 100 / effort.num_minutes().max(1)).min(100)),
+        ),
+        None => (tooltip, percentage),
+    };
+
+    let mut class = match pomodoro {
+        Some((pomodoro, _)) if pomodoro.phase == Phase::Work => vec![String::from("work")],
+        Some((pomodoro, _)) if pomodoro.is_break() => vec![String::from("break")],
+        _ => vec![],
+    };
+
+    let alert_time = config.map_or(0, |config| i64::from(config.alert_time));
+    if let Some(elapsed) = machine.elapsed(now) {
+        let minutes = elapsed.num_minutes();
+        if alert_time > 0 && minutes >= 2 * alert_time {
+            class.push(String::from("critical"));
+        } else if alert_time > 0 && minutes >= alert_time {
+            class.push(String::from("alert"));
+        }
+    }
+
+    if machine.is_untracked_too_long(now)
+        || effort_progress.is_some_and(|(clocked, effort)| clocked > effort)
+    {
+        class.push(String::from("warning"));
+    }
+
+    WaybarResponse {
+        text,
+        tooltip,
+        class,
+        percentage,
+    }
+}
+
+/// Builds a tooltip with the time clocked today and this week per task, together with the
+/// progress towards the daily target in percent.
+fn totals(
+    entries: &[ClockEntry],
+    clock: Option<&Clock>,
+    config: Option<&TimeTrackingConfig>,
+    now: NaiveDateTime,
+) -> (String, Option<i64>) {
+    let running = clock.map(|clock| ClockEntry {
+        heading: clock.task.clone(),
+        start: clock.start.naive_local(),
+        end: None,
+        effort: clock.effort,
+    });
+    let entries = entries.iter().cloned().chain(running).collect::<Vec<_>>();
+
+    let today = clocked_time(&entries, now.date().and_time(NaiveTime::MIN), now);
+    let week = clocked_time(&entries, week_start(now), now);
+    let today_total: Duration = today.iter().map(|(_, duration)| *duration).sum();
+    let week_total: Duration = week.iter().map(|(_, duration)| *duration).sum();
+
+    let daily_target = config
+        .and_then(|config| config.daily_target)
+        .filter(|target| *target > 0);
+
+    let today_summary = match daily_target {
+        Some(target) => format!(
+            "Today: {} of {}",
+            format_duration(today_total),
+            format_duration(Duration::minutes(target.into()))
+        ),
+        None => format!("Today: {}", format_duration(today_total)),
+    };
+
+    let rows = week
+        .iter()
+        .map(|(heading, week_duration)| {
+            let today_duration = today
+                .iter()
+                .find(|(today_heading, _)| today_heading == heading)
+                .map_or(Duration::zero(), |(_, duration)| *duration);
+            vec![
+                heading.clone(),
+                format_duration(today_duration),
+                format_duration(*week_duration),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let tooltip = format!(
+        "{today_summary}\nThis week: {}\n\n{}",
+        format_duration(week_total),
+        columnize_output(
+            &rows,
+            &[
+                String::from("Task"),
+                String::from("Today"),
+                String::from("Week"),
+            ]
+        )
+    );
+
+    let percentage =
+        daily_target.map(|target| (today_total.num_minutes() * 100 / i64::from(target)).min(100));
+
+    (tooltip, percentage)
+}
+
+fn week_start(now: NaiveDateTime) -> NaiveDateTime {
+    let today = now.date();
+    (today - Duration::days(today.weekday().num_days_from_monday().into())).and_time(NaiveTime::MIN)
+}
+
+fn format_countdown(remaining: Duration) -> String {
+    let remaining = remaining.max(Duration::zero());
+    format!(
+        "{:02}:{:02}",
+        remaining.num_minutes(),
+        remaining.num_seconds() % 60
+    )
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!(
+        "{}:{:02}",
+        duration.num_hours(),
+        duration.num_minutes() % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn entry(heading: &str, start: NaiveDateTime, end: NaiveDateTime) -> ClockEntry {
+        ClockEntry {
+            heading: heading.to_owned(),
+            start,
+            end: Some(end),
+            effort: None,
+        }
+    }
+
+    fn config(daily_target: Option<u32>) -> TimeTrackingConfig {
+        let mut config: TimeTrackingConfig =
+            toml::from_str("notify_time = 0\nnotify_interval = 0\nalert_time = 0").unwrap();
+        config.daily_target = daily_target;
+        config
+    }
+
+    #[test]
+    fn starts_weeks_on_monday_at_midnight() {
+        let monday = at("2024-04-29", "00:00");
+
+        assert_eq!(week_start(at("2024-04-29", "00:00")), monday);
+        assert_eq!(week_start(at("2024-05-01", "15:30")), monday);
+        assert_eq!(week_start(at("2024-05-05", "23:59")), monday);
+        assert_eq!(
+            week_start(at("2024-05-06", "08:00")),
+            at("2024-05-06", "00:00")
+        );
+    }
+
+    #[test]
+    fn sums_today_and_this_week_including_the_running_clock() {
+        let entries = [
+            // Only the part after the start of the week counts
+            entry(
+                "Plan week",
+                at("2024-04-28", "23:00"),
+                at("2024-04-29", "01:00"),
+            ),
+            // Only the part after midnight counts for today
+            entry(
+                "Write report",
+                at("2024-04-30", "23:30"),
+                at("2024-05-01", "00:30"),
+            ),
+        ];
+        let clock = Clock {
+            task: String::from("Write report"),
+            start: Local
+                .from_local_datetime(&at("2024-05-01", "10:00"))
+                .unwrap(),
+            effort: None,
+            clocked_before: Duration::zero(),
+        };
+
+        let (tooltip, percentage) = totals(
+            &entries,
+            Some(&clock),
+            Some(&config(Some(600))),
+            at("2024-05-01", "12:00"),
+        );
+
+        assert_eq!(
+            tooltip,
+            [
+                "Today: 2:30 of 10:00",
+                "This week: 4:00",
+                "",
+                "<b>Task        </b>    <b>Today</b>    <b>Week</b>",
+                "Write report    2:30     3:00",
+                "Plan week       0:00     1:00",
+            ]
+            .join("\n")
+        );
+        assert_eq!(percentage, Some(25));
+    }
+
+    #[test]
+    fn caps_the_daily_target_percentage() {
+        let entries = [entry(
+            "Write report",
+            at("2024-05-01", "08:00"),
+            at("2024-05-01", "11:00"),
+        )];
+        let now = at("2024-05-01", "12:00");
+
+        let percentage = |daily_target| totals(&entries, None, Some(&config(daily_target)), now).1;
+
+        assert_eq!(percentage(Some(120)), Some(100));
+        assert_eq!(percentage(Some(240)), Some(75));
+        assert_eq!(percentage(Some(0)), None);
+        assert_eq!(percentage(None), None);
+        assert_eq!(totals(&entries, None, None, now).1, None);
+    }
+}
@@ -18,10 +18,12 @@ pub struct WaybarResponse {
 const IGNORE_STATUSES: Status = Status::IGNORED.union(Status::WT_DELETED);
 
 fn main() {
-    let config = Config::load("waybar", "modules.toml").expect("Could not load configuration");
+    let config = Config::load("waybar", "modules.toml")
+        .expect("Could not load configuration")
+        .unfinished_projects
+        .expect("Missing unfinished-projects section");
 
     let repos = config
-        .unfinished_projects
         .project_dirs
         .iter()
         .flat_map(|project_dir| {
@@ -45,11 +47,7 @@ fn main() {
                                 .then_some((project_path.join(path), *status))
                         })
                         .map(|(path, status)| {
-                            (
-                                path.clone(),
-                                path_age(&path, config.unfinished_projects.max_file_depth),
-                                status,
-                            )
+                            (path.clone(), path_age(&path, config.max_file_depth), status)
                         })
                         .collect::<Vec<_>>();
 
@@ -66,11 +64,11 @@ fn main() {
         .max();
 
     let age_class = if let Some(Some(oldest_age)) = oldest_file {
-        if *oldest_age > Duration::new(86400 * config.unfinished_projects.critical_age, 0) {
+        if *oldest_age > Duration::new(86400 * config.critical_age, 0) {
             Some("critical")
-        } else if *oldest_age > Duration::new(86400 * config.unfinished_projects.warning_age, 0) {
+        } else if *oldest_age > Duration::new(86400 * config.warning_age, 0) {
             Some("warning")
-        } else if *oldest_age > Duration::new(86400 * config.unfinished_projects.active_age, 0) {
+        } else if *oldest_age > Duration::new(86400 * config.active_age, 0) {
             Some("active")
         } else {
             None
@@ -86,7 +84,7 @@ fn main() {
                 .iter()
                 .max_by_key(|(_path, age, _status)| age)
             {
-                *max_age > Duration::new(86400 * config.unfinished_projects.active_age, 0)
+                *max_age > Duration::new(86400 * config.active_age, 0)
             } else {
                 false
             }
@@ -1,6 +1,6 @@
 use waybar_extensions::weather;
 
-use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 
 #[derive(serde::Serialize)]
 pub struct WaybarResponse {
@@ -20,13 +20,14 @@ async fn main() {
     let config = waybar_extensions::config::Config::from_file(&config_path)
         .expect("Could not read configuration");
 
-    let weather = weather::current::get(
-        config.general.lat,
-        config.general.lon,
-        &config.openweathermap.api_key,
-    )
-    .await
-    .expect("No current weather found");
+    let general = config.general.expect("Missing general section");
+    let openweathermap = config
+        .openweathermap
+        .expect("Missing openweathermap section");
+
+    let weather = weather::current::get(general.lat, general.lon, &openweathermap.api_key)
+        .await
+        .expect("No current weather found");
 
     let temp = weather.main.temp - 273.15;
     let description = weather.weather[0].main.clone();
@@ -43,18 +44,12 @@ async fn main() {
     let min_temp = weather.main.temp_min - 273.15;
     let max_temp = weather.main.temp_max - 273.15;
 
-    let sunrise = NaiveDateTime::from_timestamp_opt(weather.sys.sunrise, 0)
-        .map(|dt_naive| {
-            let dt_utc = DateTime::<Utc>::from_utc(dt_naive, Utc);
-            format!("{}", DateTime::<Local>::from(dt_utc).format("%H:%M"))
-        })
+    let sunrise = DateTime::<Utc>::from_timestamp(weather.sys.sunrise, 0)
+        .map(|dt_utc| format!("{}", DateTime::<Local>::from(dt_utc).format("%H:%M")))
         .unwrap_or("-".into());
 
-    let sunset = NaiveDateTime::from_timestamp_opt(weather.sys.sunset, 0)
-        .map(|dt_naive| {
-            let dt_utc = DateTime::<Utc>::from_utc(dt_naive, Utc);
-            format!("{}", DateTime::<Local>::from(dt_utc).format("%H:%M"))
-        })
+    let sunset = DateTime::<Utc>::from_timestamp(weather.sys.sunset, 0)
+        .map(|dt_utc| format!("{}", DateTime::<Local>::from(dt_utc).format("%H:%M")))
         .unwrap_or("-".into());
 
     let tooltip = format!("Rain: {rain_info} mm\nWind: {wind_speed} km/h ({wind_direction})\nTemperature: {min_temp:.1} - {max_temp:.1} °C\nSunrise: {sunrise}\nSunset: {sunset}");
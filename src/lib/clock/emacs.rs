@@ -0,0 +1,213 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+
+use super::{Clock, ClockBackend};
+use crate::config::{ClockSource, TimeTrackingConfig};
+use crate::emacs::{quote_string, read_string, Emacs};
+use crate::org::{clock_entries, open_clock, ClockEntry};
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// Reads all clock state at once, so the task and start time always belong together
+const CLOCK_STATE: &str = r#"(progn
+  (require 'json)
+  (json-encode
+   (if (and (fboundp 'org-clocking-p) (org-clocking-p))
+       `((running . t)
+         (task . ,(substring-no-properties org-clock-heading))
+         (start . ,(float-time org-clock-start-time))
+         (effort . ,(and org-clock-effort
+                         (not (string-empty-p org-clock-effort))
+                         (org-duration-to-minutes org-clock-effort)))
+         (clocked . ,org-clock-total-time))
+     '((running . :json-false)))))"#;
+
+/// Lists the headings of the recently clocked tasks, most recent first
+const RECENT_TASKS: &str = r#"(progn
+  (require 'json)
+  (require 'org-clock)
+  (json-encode
+   (vconcat
+    (delete-dups
+     (delq nil
+           (mapcar (lambda (marker)
+                     (when (marker-buffer marker)
+                       (with-current-buffer (marker-buffer marker)
+                         (org-with-wide-buffer
+                          (goto-char marker)
+                          (org-get-heading t t t t)))))
+                   org-clock-history))))))"#;
+
+const CLOCK_IN_LAST: &str = "(progn (require 'org-clock) (org-clock-in-last))";
+
+/// Lists the agenda files of Emacs, to read the totals from when none are configured
+const AGENDA_FILES: &str = r#"(progn
+  (require 'json)
+  (json-encode
+   (vconcat (and (fboundp 'org-agenda-files) (org-agenda-files)))))"#;
+
+#[derive(Deserialize)]
+struct ClockState {
+    running: bool,
+    task: Option<String>,
+    start: Option<f64>,
+    /// Effort estimate in minutes
+    effort: Option<f64>,
+    /// Minutes clocked on the task before the running clock
+    clocked: Option<i64>,
+}
+
+/// The org clock, asked from Emacs or read from the agenda files.
+pub struct EmacsBackend {
+    emacs: Emacs,
+    source: ClockSource,
+    agenda_files: Vec<String>,
+}
+
+impl EmacsBackend {
+    pub fn new(config: Option<&TimeTrackingConfig>) -> Self {
+        match config {
+            Some(config) => Self {
+                emacs: Emacs::new(config.connection.clone(), config.socket.clone()),
+                source: config.source,
+                agenda_files: config.agenda_files.clone(),
+            },
+            None => Self {
+                emacs: Emacs::new(Default::default(), None),
+                source: ClockSource::Emacs,
+                agenda_files: vec![],
+            },
+        }
+    }
+
+    async fn emacs_clock(&self) -> Result<Option<Clock>, BoxedError> {
+        let clock_state: ClockState =
+            serde_json::from_str(&read_string(&self.emacs.eval(CLOCK_STATE).await?)?)?;
+
+        if !clock_state.running {
+            return Ok(None);
+        }
+
+        let start_time_float = clock_state
+            .start
+            .ok_or("Clock is running without start time")?;
+        let start_time_utc = DateTime::<Utc>::from_timestamp(
+            start_time_float as i64,
+            (start_time_float % 1f64 * 1e9) as u32,
+        )
+        .ok_or("Could not convert timestamp to datetime")?;
+
+        Ok(Some(Clock {
+            task: clock_state.task.unwrap_or_default(),
+            start: DateTime::<Local>::from(start_time_utc),
+            effort: clock_state
+                .effort
+                .map(|effort| Duration::minutes(effort.round() as i64)),
+            clocked_before: Duration::minutes(clock_state.clocked.unwrap_or_default()),
+        }))
+    }
+
+    fn file_clock(&self) -> Result<Option<Clock>, BoxedError> {
+        let entries = clock_entries(&self.agenda_files)?;
+        let Some(entry) = open_clock(&entries) else {
+            return Ok(None);
+        };
+
+        let clocked_before = entries
+            .iter()
+            .filter(|other| other.heading == entry.heading)
+            .filter_map(|other| Some(other.end? - other.start))
+            .sum();
+
+        let start = Local
+            .from_local_datetime(&entry.start)
+            .earliest()
+            .ok_or("Clock start time does not exist in the local timezone")?;
+
+        Ok(Some(Clock {
+            task: entry.heading.clone(),
+            start,
+            effort: entry.effort,
+            clocked_before,
+        }))
+    }
+}
+
+#[async_trait(?Send)]
+impl ClockBackend for EmacsBackend {
+    async fn clock(&self) -> Result<Option<Clock>, BoxedError> {
+        match self.source {
+            ClockSource::Files => self.file_clock(),
+            ClockSource::Emacs if !self.agenda_files.is_empty() => match self.emacs_clock().await {
+                Ok(clock) => Ok(clock),
+                Err(_) => self.file_clock(),
+            },
+            ClockSource::Emacs => self.emacs_clock().await,
+        }
+    }
+
+    /// Reads the entries from the configured agenda files, or from the ones Emacs uses.
+    async fn entries(&self, since: NaiveDateTime) -> Result<Vec<ClockEntry>, BoxedError> {
+        let paths: Vec<String> =
+            if self.source == ClockSource::Files || !self.agenda_files.is_empty() {
+                self.agenda_files.clone()
+            } else {
+                serde_json::from_str(&read_string(&self.emacs.eval(AGENDA_FILES).await?)?)?
+            };
+
+        Ok(clock_entries(&paths)?
+            .into_iter()
+            .filter(|entry| entry.end.is_some_and(|end| end > since))
+            .collect())
+    }
+
+    async fn recent_tasks(&self) -> Result<Vec<String>, BoxedError> {
+        Ok(serde_json::from_str(&read_string(
+            &self.emacs.eval(RECENT_TASKS).await?,
+        )?)?)
+    }
+
+    /// Clocks into the most recent task in the clock history with the given heading.
+    async fn clock_in(&self, task: &str) -> Result<(), BoxedError> {
+        let expression = format!(
+            r#"(progn
+  (require 'org-clock)
+  (let ((marker (seq-find
+                 (lambda (marker)
+                   (and (marker-buffer marker)
+                        (with-current-buffer (marker-buffer marker)
+                          (org-with-wide-buffer
+                           (goto-char marker)
+                           (equal (org-get-heading t t t t) {task})))))
+                 org-clock-history)))
+    (unless marker
+      (error "No recently clocked task %s" {task}))
+    (with-current-buffer (marker-buffer marker)
+      (org-with-wide-buffer
+       (goto-char marker)
+       (org-clock-in)))))"#,
+            task = quote_string(task)
+        );
+
+        self.emacs.eval(&expression).await?;
+        Ok(())
+    }
+
+    async fn clock_in_last(&self) -> Result<(), BoxedError> {
+        self.emacs.eval(CLOCK_IN_LAST).await?;
+        Ok(())
+    }
+
+    async fn clock_out(&self, at: Option<DateTime<Local>>) -> Result<(), BoxedError> {
+        let at = at.map_or(String::from("nil"), |at| {
+            format!("(seconds-to-time {})", at.timestamp())
+        });
+        self.emacs
+            .eval(&format!(
+                "(progn (require 'org-clock) (when (org-clocking-p) (org-clock-out nil t {at})))"
+            ))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDateTime, SecondsFormat};
+
+use super::{Clock, ClockBackend};
+use crate::org::ClockEntry;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// A line of the file: start, end and task separated by tabs, where the end is left empty while
+/// the clock runs, e.g. `2024-05-01T09:12:00+02:00\t\tWrite report`.
+#[derive(Debug, PartialEq)]
+struct Line {
+    start: DateTime<Local>,
+    end: Option<DateTime<Local>>,
+    task: String,
+}
+
+impl Line {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, '\t');
+        let start = DateTime::parse_from_rfc3339(fields.next()?.trim()).ok()?;
+        let end = match fields.next()?.trim() {
+            "" => None,
+            end => Some(DateTime::parse_from_rfc3339(end).ok()?.into()),
+        };
+
+        Some(Self {
+            start: start.into(),
+            end,
+            task: fields.next()?.trim().to_owned(),
+        })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}",
+            self.start.to_rfc3339_opts(SecondsFormat::Secs, false),
+            self.end
+                .map(|end| end.to_rfc3339_opts(SecondsFormat::Secs, false))
+                .unwrap_or_default(),
+            self.task
+        )
+    }
+}
+
+/// Keeps the clock in a plain text file, for when there is no other time tracker. The file can
+/// be edited by hand, lines that cannot be read are left alone.
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    /// Uses the given file, or `time-tracking.tsv` in the XDG data directory.
+    pub fn new(path: Option<PathBuf>) -> Result<Self, BoxedError> {
+        let path = match path {
+            Some(path) => path,
+            None => xdg::BaseDirectories::with_prefix("waybar")
+                .or(Err("Could not load app data directory"))?
+                .place_data_file("time-tracking.tsv")?,
+        };
+
+        Ok(Self { path })
+    }
+
+    fn read(&self) -> Result<Vec<String>, BoxedError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => Ok(text.lines().map(str::to_owned).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the file through a temporary one next to it, so an interrupted write does not
+    /// lose the clock.
+    fn write(&self, lines: &[String]) -> Result<(), BoxedError> {
+        let mut text = lines.join("\n");
+        text.push('\n');
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, text)?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    fn parse_lines(&self) -> Result<Vec<Line>, BoxedError> {
+        Ok(self
+            .read()?
+            .iter()
+            .filter_map(|line| Line::parse(line))
+            .collect())
+    }
+
+    /// Ends the running clock, if any, at `at`. Returns the rewritten lines.
+    fn stop_running(&self, at: DateTime<Local>) -> Result<Vec<String>, BoxedError> {
+        let mut lines = self.read()?;
+        for line in lines.iter_mut() {
+            if let Some(mut entry) = Line::parse(line).filter(|entry| entry.end.is_none()) {
+                entry.end = Some(at.max(entry.start));
+                *line = entry.format();
+            }
+        }
+
+        Ok(lines)
+    }
+}
+
+#[async_trait(?Send)]
+impl ClockBackend for FileBackend {
+    async fn clock(&self) -> Result<Option<Clock>, BoxedError> {
+        let entries = self.parse_lines()?;
+        let Some(running) = entries.iter().rev().find(|entry| entry.end.is_none()) else {
+            return Ok(None);
+        };
+
+        let clocked_before = entries
+            .iter()
+            .filter(|entry| entry.task == running.task)
+            .filter_map(|entry| Some(entry.end? - entry.start))
+            .sum();
+
+        Ok(Some(Clock {
+            task: running.task.clone(),
+            start: running.start,
+            effort: None,
+            clocked_before,
+        }))
+    }
+
+    async fn entries(&self, since: NaiveDateTime) -> Result<Vec<ClockEntry>, BoxedError> {
+        Ok(self
+            .parse_lines()?
+            .into_iter()
+            .filter_map(|entry| {
+                let end = entry.end?.naive_local();
+                (end > since).then(|| ClockEntry {
+                    heading: entry.task,
+                    start: entry.start.naive_local(),
+                    end: Some(end),
+                    effort: None,
+                })
+            })
+            .collect())
+    }
+
+    async fn recent_tasks(&self) -> Result<Vec<String>, BoxedError> {
+        let mut tasks = vec![];
+        for entry in self.parse_lines()?.into_iter().rev() {
+            if !tasks.contains(&entry.task) {
+                tasks.push(entry.task);
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    /// Starts a clock on the task, stopping the running one like clocking in with org does.
+    async fn clock_in(&self, task: &str) -> Result<(), BoxedError> {
+        let now = Local::now();
+        let mut lines = self.stop_running(now)?;
+        lines.push(
+            Line {
+                start: now,
+                end: None,
+                task: task.replace(['\t', '\n'], " "),
+            }
+            .format(),
+        );
+
+        self.write(&lines)
+    }
+
+    async fn clock_in_last(&self) -> Result<(), BoxedError> {
+        let task = self
+            .recent_tasks()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or("No task has been clocked yet")?;
+
+        self.clock_in(&task).await
+    }
+
+    async fn clock_out(&self, at: Option<DateTime<Local>>) -> Result<(), BoxedError> {
+        let lines = self.stop_running(at.unwrap_or_else(Local::now))?;
+        self.write(&lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, hour, minute, 0).unwrap()
+    }
+
+    /// A backend on a file of its own, removed again when dropped
+    struct TestFile(FileBackend);
+
+    impl TestFile {
+        fn new(name: &str, text: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "waybar-extensions-{name}-{}.tsv",
+                std::process::id()
+            ));
+            std::fs::write(&path, text).unwrap();
+            Self(FileBackend::new(Some(path)).unwrap())
+        }
+    }
+
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0.path);
+        }
+    }
+
+    #[test]
+    fn formats_lines_that_parse_again() {
+        let closed = Line {
+            start: at(9, 12),
+            end: Some(at(10, 30)),
+            task: String::from("Write report"),
+        };
+        let running = Line {
+            start: at(11, 0),
+            end: None,
+            task: String::from("Review pull request"),
+        };
+
+        assert_eq!(Line::parse(&closed.format()), Some(closed));
+        assert_eq!(Line::parse(&running.format()), Some(running));
+    }
+
+    #[test]
+    fn parses_lines_in_any_offset() {
+        let line =
+            Line::parse("2024-05-01T07:12:00Z\t2024-05-01T10:30:00+02:00\t Write report ").unwrap();
+
+        assert_eq!(
+            line.start,
+            DateTime::parse_from_rfc3339("2024-05-01T09:12:00+02:00").unwrap()
+        );
+        assert_eq!(
+            line.end,
+            Some(
+                DateTime::parse_from_rfc3339("2024-05-01T08:30:00Z")
+                    .unwrap()
+                    .into()
+            )
+        );
+        assert_eq!(line.task, "Write report");
+    }
+
+    #[test]
+    fn rejects_lines_it_cannot_read() {
+        assert_eq!(Line::parse(""), None);
+        assert_eq!(Line::parse("# Notes on this week"), None);
+        assert_eq!(Line::parse("2024-05-01T09:12:00Z"), None);
+        assert_eq!(
+            Line::parse("2024-05-01T09:12:00Z\t\t"),
+            Some(Line {
+                start: DateTime::parse_from_rfc3339("2024-05-01T09:12:00Z")
+                    .unwrap()
+                    .into(),
+                end: None,
+                task: String::new(),
+            })
+        );
+        assert_eq!(Line::parse("yesterday\t\tWrite report"), None);
+        assert_eq!(
+            Line::parse("2024-05-01T09:12:00Z\tlater\tWrite report"),
+            None
+        );
+    }
+
+    #[test]
+    fn stops_running_clock_no_earlier_than_its_start() {
+        let running = Line {
+            start: at(11, 0),
+            end: None,
+            task: String::from("Review pull request"),
+        };
+        let file = TestFile::new(
+            "stop-running",
+            &format!("# Edited by hand\n{}\n", running.format()),
+        );
+
+        let lines = file.0.stop_running(at(10, 0)).unwrap();
+
+        assert_eq!(lines[0], "# Edited by hand");
+        assert_eq!(
+            Line::parse(&lines[1]),
+            Some(Line {
+                end: Some(at(11, 0)),
+                ..running
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn replaces_the_file_on_clock_out() {
+        let running = Line {
+            start: at(11, 0),
+            end: None,
+            task: String::from("Review pull request"),
+        };
+        let file = TestFile::new("clock-out", &format!("{}\n", running.format()));
+
+        file.0.clock_out(Some(at(12, 0))).await.unwrap();
+
+        let mut temporary = file.0.path.clone().into_os_string();
+        temporary.push(".tmp");
+        assert!(!PathBuf::from(temporary).exists());
+        assert_eq!(file.0.clock().await.unwrap(), None);
+        assert_eq!(
+            std::fs::read_to_string(&file.0.path).unwrap(),
+            format!(
+                "{}\n",
+                Line {
+                    end: Some(at(12, 0)),
+                    ..running
+                }
+                .format()
+            )
+        );
+    }
+}
@@ -0,0 +1,947 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+
+use crate::config::{PomodoroConfig, TimeTrackingBackend, TimeTrackingConfig};
+use crate::org::ClockEntry;
+
+pub mod emacs;
+pub mod file;
+pub mod timewarrior;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// A gap between two ticks longer than this means the machine was suspended, or the wall clock
+/// jumped
+const SUSPEND_THRESHOLD: Duration = Duration::seconds(30);
+
+/// The running clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clock {
+    pub task: String,
+    pub start: DateTime<Local>,
+    /// Estimate of the time the task takes
+    pub effort: Option<Duration>,
+    /// Time spent on the task before the running clock started
+    pub clocked_before: Duration,
+}
+
+/// Where the current time comes from, so the schedule can be driven by something else than the
+/// wall clock.
+pub trait TimeSource {
+    fn now(&self) -> DateTime<Local>;
+}
+
+pub struct SystemTime;
+
+impl TimeSource for SystemTime {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// Reads and controls the clock of a time tracker, e.g. the org clock through Emacs.
+#[async_trait(?Send)]
+pub trait ClockBackend {
+    async fn clock(&self) -> Result<Option<Clock>, BoxedError>;
+
+    /// Finished clock entries that ended after `since`.
+    async fn entries(&self, since: NaiveDateTime) -> Result<Vec<ClockEntry>, BoxedError>;
+
+    /// Headings of the recently clocked tasks, most recent first.
+    async fn recent_tasks(&self) -> Result<Vec<String>, BoxedError>;
+
+    async fn clock_in(&self, task: &str) -> Result<(), BoxedError>;
+
+    async fn clock_in_last(&self) -> Result<(), BoxedError>;
+
+    /// Stops the running clock, now or at an earlier moment.
+    async fn clock_out(&self, at: Option<DateTime<Local>>) -> Result<(), BoxedError>;
+
+    /// Takes the time since `since` off the running clock and keeps the task clocked in.
+    async fn discard_since(&self, since: DateTime<Local>) -> Result<(), BoxedError> {
+        self.clock_out(Some(since)).await?;
+        self.clock_in_last().await
+    }
+}
+
+/// Sets up the configured backend, the org clock when there is no configuration.
+pub fn create_backend(
+    config: Option<&TimeTrackingConfig>,
+) -> Result<Box<dyn ClockBackend>, BoxedError> {
+    match config.map(|config| config.backend) {
+        None | Some(TimeTrackingBackend::Org) => Ok(Box::new(emacs::EmacsBackend::new(config))),
+        Some(TimeTrackingBackend::Timewarrior) => Ok(Box::new(timewarrior::TimewarriorBackend)),
+        Some(TimeTrackingBackend::File) => Ok(Box::new(file::FileBackend::new(
+            config.and_then(|config| config.file.clone()),
+        )?)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// Waiting for a clock to start the next work period
+    Idle,
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Pomodoro {
+    pub phase: Phase,
+    pub started: DateTime<Local>,
+    /// Work periods finished since the last long break
+    pub completed: u32,
+}
+
+impl Pomodoro {
+    fn new(now: DateTime<Local>) -> Self {
+        Self {
+            phase: Phase::Idle,
+            started: now,
+            completed: 0,
+        }
+    }
+
+    pub fn length(&self, config: &PomodoroConfig) -> Duration {
+        let minutes = match self.phase {
+            Phase::Idle => return Duration::zero(),
+            Phase::Work => config.work,
+            Phase::ShortBreak => config.short_break,
+            Phase::LongBreak => config.long_break,
+        };
+        Duration::minutes(minutes.into())
+    }
+
+    pub fn remaining(&self, config: &PomodoroConfig, now: DateTime<Local>) -> Duration {
+        self.length(config) - (now - self.started)
+    }
+
+    pub fn is_break(&self) -> bool {
+        matches!(self.phase, Phase::ShortBreak | Phase::LongBreak)
+    }
+
+    /// Moves on to the next phase when the current one is over. Work only counts while the clock
+    /// is running, a break runs out regardless.
+    fn advance(self, config: &PomodoroConfig, clock: Option<&Clock>, now: DateTime<Local>) -> Self {
+        match (self.phase, clock) {
+            // A clock started within the last work period, e.g. before the module restarted,
+            // keeps its progress
+            (Phase::Idle, Some(clock)) => Self {
+                phase: Phase::Work,
+                started: if now - clock.start < Duration::minutes(config.work.into()) {
+                    clock.start.min(now)
+                } else {
+                    now
+                },
+                ..self
+            },
+            (Phase::Work, None) => Self {
+                phase: Phase::Idle,
+                ..self
+            },
+            (Phase::Work, Some(_)) if self.remaining(config, now) <= Duration::zero() => {
+                let completed = self.completed + 1;
+                if config.long_break_after > 0 && completed >= config.long_break_after {
+                    Self {
+                        phase: Phase::LongBreak,
+                        started: now,
+                        completed: 0,
+                    }
+                } else {
+                    Self {
+                        phase: Phase::ShortBreak,
+                        started: now,
+                        completed,
+                    }
+                }
+            }
+            (Phase::ShortBreak | Phase::LongBreak, _)
+                if self.remaining(config, now) <= Duration::zero() =>
+            {
+                Self {
+                    phase: if clock.is_some() {
+                        Phase::Work
+                    } else {
+                        Phase::Idle
+                    },
+                    started: now,
+                    ..self
+                }
+            }
+            _ => self,
+        }
+    }
+}
+
+/// When the next break notification is due
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BreakSchedule {
+    /// Not worked out yet for the current clock
+    Unscheduled,
+    At(DateTime<Local>),
+    /// No more notifications for the current clock
+    Finished,
+}
+
+/// Something that happened to the clock and deserves a notification or action.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The clock has been running long enough for a break
+    BreakDue { worked: Duration },
+    /// A pomodoro work period is over
+    BreakStarted { phase: Phase, length: Duration },
+    /// A pomodoro break is over, the next work period starts when clocked in
+    BreakOver { clocked_in: bool },
+    /// No clock has been running for a while during work hours
+    Untracked { untracked: Duration },
+    /// The session is in use again after being idle while clocked in
+    IdleReturned { since: DateTime<Local> },
+}
+
+/// Everything the module knows about the clock, changed only through the current time and what
+/// the backend reports. Side effects are left to the caller through the returned events.
+pub struct ClockMachine<'a> {
+    config: Option<&'a TimeTrackingConfig>,
+    clock: Option<Clock>,
+    /// Finished clock entries of this week
+    entries: Vec<ClockEntry>,
+    pomodoro: Pomodoro,
+    untracked_since: Option<DateTime<Local>>,
+    reminders_sent: i64,
+    next_break_notification: BreakSchedule,
+    idle_since: Option<DateTime<Local>>,
+    last_tick: Option<DateTime<Local>>,
+}
+
+impl<'a> ClockMachine<'a> {
+    pub fn new(config: Option<&'a TimeTrackingConfig>, now: DateTime<Local>) -> Self {
+        Self {
+            config,
+            clock: None,
+            entries: vec![],
+            pomodoro: Pomodoro::new(now),
+            untracked_since: None,
+            reminders_sent: 0,
+            next_break_notification: BreakSchedule::Unscheduled,
+            idle_since: None,
+            last_tick: None,
+        }
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    pub fn entries(&self) -> &[ClockEntry] {
+        &self.entries
+    }
+
+    /// The pomodoro, when that mode is enabled.
+    pub fn pomodoro(&self) -> Option<(&Pomodoro, &'a PomodoroConfig)> {
+        self.pomodoro_config()
+            .map(|pomodoro_config| (&self.pomodoro, pomodoro_config))
+    }
+
+    /// Time on the running clock, never negative even when the clock was started in the future
+    /// as far as this machine knows.
+    pub fn elapsed(&self, now: DateTime<Local>) -> Option<Duration> {
+        self.clock
+            .as_ref()
+            .map(|clock| (now - clock.start).max(Duration::zero()))
+    }
+
+    /// Total time spent on the clocked task together with its effort estimate, when it has one.
+    pub fn effort_progress(&self, now: DateTime<Local>) -> Option<(Duration, Duration)> {
+        let clock = self.clock.as_ref()?;
+        let effort = clock.effort.filter(|effort| *effort > Duration::zero())?;
+
+        Some((clock.clocked_before + self.elapsed(now)?, effort))
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Clock>, now: DateTime<Local>) {
+        self.untracked_since = match (&clock, self.untracked_since) {
+            (Some(_), _) => None,
+            (None, Some(since)) => Some(since),
+            (None, None) => Some(now),
+        };
+
+        // Changes to the effort or the time clocked before keep the schedule of the clock
+        if clock.as_ref().map(|clock| (&clock.task, clock.start))
+            != self.clock.as_ref().map(|clock| (&clock.task, clock.start))
+        {
+            self.next_break_notification = BreakSchedule::Unscheduled;
+        }
+        self.clock = clock;
+    }
+
+    /// Puts off the next break notification until `until`, or until the clock changes.
+    pub fn snooze(&mut self, until: DateTime<Local>) {
+        self.next_break_notification = BreakSchedule::At(until);
+    }
+
+    pub fn set_entries(&mut self, entries: Vec<ClockEntry>) {
+        self.entries = entries;
+    }
+
+    /// Takes in when the session went idle, `None` while it is in use. Returns an event once the
+    /// session is in use again after at least `idle_time` minutes.
+    pub fn set_idle(
+        &mut self,
+        idle_since: Option<DateTime<Local>>,
+        now: DateTime<Local>,
+    ) -> Option<Event> {
+        let idle_time = self.config?.idle_time?;
+
+        match (idle_since, &self.clock) {
+            (Some(since), Some(clock)) => {
+                self.idle_since = Some(since.max(clock.start));
+                None
+            }
+            // Without a clock there is no idle time to discard
+            (_, None) => {
+                self.idle_since = None;
+                None
+            }
+            (None, Some(clock)) => {
+                let since = self.idle_since.take()?;
+                // A clock started again while away has not run the whole time
+                (clock.start <= since && (now - since).num_minutes() >= idle_time.into())
+                    .then_some(Event::IdleReturned { since })
+            }
+        }
+    }
+
+    /// Advances the schedule to `now`. A jump in time, from a suspend or the wall clock being
+    /// set, skips whatever would have happened in between instead of catching up on it.
+    pub fn tick(&mut self, now: DateTime<Local>) -> Vec<Event> {
+        let mut events = vec![];
+
+        let resumed = self
+            .last_tick
+            .is_some_and(|last_tick| now < last_tick || now - last_tick > SUSPEND_THRESHOLD);
+        self.last_tick = Some(now);
+
+        if resumed {
+            self.resume(now);
+        }
+
+        if let Some(pomodoro_config) = self.pomodoro_config() {
+            let previous = self.pomodoro;
+            self.pomodoro = previous.advance(pomodoro_config, self.clock.as_ref(), now);
+            events.extend(self.pomodoro_event(previous.phase, pomodoro_config));
+        } else if let Some(event) = self.break_notification(now) {
+            events.push(event);
+        }
+
+        if let Some(event) = self.untracked_reminder(now) {
+            events.push(event);
+        }
+
+        events
+    }
+
+    fn resume(&mut self, now: DateTime<Local>) {
+        // Notifications missed while away are not sent, the next one comes at its usual time
+        if let (BreakSchedule::At(_), Some(config), Some(worked)) =
+            (self.next_break_notification, self.config, self.elapsed(now))
+        {
+            self.next_break_notification = match notify_delay(config, worked) {
+                Some(delay) => BreakSchedule::At(now + delay),
+                None => BreakSchedule::Finished,
+            };
+        }
+        self.reminders_sent = self.reminders_due(now);
+
+        // Whatever phase was running has been spent away from the computer
+        if let Some(pomodoro_config) = self.pomodoro_config() {
+            if self.pomodoro.phase != Phase::Idle
+                && self.pomodoro.remaining(pomodoro_config, now) <= Duration::zero()
+            {
+                self.pomodoro = Pomodoro {
+                    completed: self.pomodoro.completed,
+                    ..Pomodoro::new(now)
+                };
+            }
+        }
+    }
+
+    fn pomodoro_config(&self) -> Option<&'a PomodoroConfig> {
+        self.config
+            .map(|config| &config.pomodoro)
+            .filter(|pomodoro_config| pomodoro_config.enabled)
+    }
+
+    fn pomodoro_event(&self, previous: Phase, pomodoro_config: &PomodoroConfig) -> Option<Event> {
+        match (previous, self.pomodoro.phase) {
+            (Phase::Work, Phase::ShortBreak | Phase::LongBreak) => Some(Event::BreakStarted {
+                phase: self.pomodoro.phase,
+                length: self.pomodoro.length(pomodoro_config),
+            }),
+            (Phase::ShortBreak | Phase::LongBreak, Phase::Work | Phase::Idle) => {
+                Some(Event::BreakOver {
+                    clocked_in: self.pomodoro.phase == Phase::Work,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Reminds to take a break after `notify_time` minutes on the clock, and every
+    /// `notify_interval` minutes after that.
+    fn break_notification(&mut self, now: DateTime<Local>) -> Option<Event> {
+        let config = self.config?;
+        let worked = self.elapsed(now)?;
+
+        let due = match self.next_break_notification {
+            BreakSchedule::At(next) => now >= next,
+            // Starting on a clock that has been running for a while reminds right away
+            BreakSchedule::Unscheduled => worked >= Duration::minutes(config.notify_time.into()),
+            BreakSchedule::Finished => return None,
+        };
+
+        if due || self.next_break_notification == BreakSchedule::Unscheduled {
+            self.next_break_notification = match notify_delay(config, worked) {
+                Some(delay) => BreakSchedule::At(now + delay),
+                None => BreakSchedule::Finished,
+            };
+        }
+
+        due.then_some(Event::BreakDue { worked })
+    }
+
+    /// Time without a clock in the current work period, together with the time after which that
+    /// deserves a warning. `None` outside of work hours.
+    pub fn untracked_time(&self, now: DateTime<Local>) -> Option<(Duration, Duration)> {
+        let work_hours = self.config?.work_hours.as_ref()?;
+        let untracked_since = self.untracked_since?;
+        let period_start = work_hours.period_start(now.naive_local())?;
+
+        let untracked = now.naive_local() - untracked_since.naive_local().max(period_start);
+        Some((
+            untracked.max(Duration::zero()),
+            Duration::minutes(work_hours.untracked_time.into()),
+        ))
+    }
+
+    pub fn is_untracked_too_long(&self, now: DateTime<Local>) -> bool {
+        self.untracked_time(now)
+            .is_some_and(|(untracked, threshold)| untracked >= threshold)
+    }
+
+    fn reminders_due(&self, now: DateTime<Local>) -> i64 {
+        match self.untracked_time(now) {
+            Some((untracked, threshold)) if threshold > Duration::zero() => {
+                untracked.num_seconds() / threshold.num_seconds()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Nudges to clock in when no clock runs during work hours, again every time the same
+    /// stretch of untracked time has passed.
+    fn untracked_reminder(&mut self, now: DateTime<Local>) -> Option<Event> {
+        let reminders_due = self.reminders_due(now);
+        let remind = reminders_due > self.reminders_sent;
+        self.reminders_sent = reminders_due;
+
+        let (untracked, _) = self.untracked_time(now)?;
+        remind.then_some(Event::Untracked { untracked })
+    }
+}
+
+/// Time until the next break notification for a clock that has been running for `elapsed`.
+/// Notifications come at `notify_time` minutes and every `notify_interval` minutes after, or
+/// only once when the interval is zero.
+pub fn notify_delay(config: &TimeTrackingConfig, elapsed: Duration) -> Option<Duration> {
+    let notify_time = Duration::minutes(config.notify_time.into());
+    let notify_interval = Duration::minutes(config.notify_interval.into());
+
+    if elapsed < notify_time {
+        return Some(notify_time - elapsed);
+    }
+
+    if notify_interval <= Duration::zero() {
+        return None;
+    }
+
+    let intervals_passed = (elapsed - notify_time).num_seconds() / notify_interval.num_seconds();
+    Some(notify_time + notify_interval * (intervals_passed as i32 + 1) - elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    struct FakeTime(Cell<DateTime<Local>>);
+
+    impl FakeTime {
+        fn new() -> Self {
+            Self(Cell::new(
+                Local.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap(),
+            ))
+        }
+
+        fn advance(&self, by: Duration) {
+            self.0.set(self.0.get() + by);
+        }
+    }
+
+    impl TimeSource for FakeTime {
+        fn now(&self) -> DateTime<Local> {
+            self.0.get()
+        }
+    }
+
+    struct FakeBackend<'a> {
+        time: &'a FakeTime,
+        clock: RefCell<Option<Clock>>,
+    }
+
+    impl<'a> FakeBackend<'a> {
+        fn new(time: &'a FakeTime) -> Self {
+            Self {
+                time,
+                clock: RefCell::new(None),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl ClockBackend for FakeBackend<'_> {
+        async fn clock(&self) -> Result<Option<Clock>, BoxedError> {
+            Ok(self.clock.borrow().clone())
+        }
+
+        async fn entries(&self, _since: NaiveDateTime) -> Result<Vec<ClockEntry>, BoxedError> {
+            Ok(vec![])
+        }
+
+        async fn recent_tasks(&self) -> Result<Vec<String>, BoxedError> {
+            Ok(vec![])
+        }
+
+        async fn clock_in(&self, task: &str) -> Result<(), BoxedError> {
+            *self.clock.borrow_mut() = Some(Clock {
+                task: task.to_owned(),
+                start: self.time.now(),
+                effort: None,
+                clocked_before: Duration::zero(),
+            });
+            Ok(())
+        }
+
+        async fn clock_in_last(&self) -> Result<(), BoxedError> {
+            self.clock_in("Last task").await
+        }
+
+        async fn clock_out(&self, _at: Option<DateTime<Local>>) -> Result<(), BoxedError> {
+            *self.clock.borrow_mut() = None;
+            Ok(())
+        }
+    }
+
+    fn config() -> TimeTrackingConfig {
+        toml::from_str("notify_time = 50\nnotify_interval = 10\nalert_time = 90").unwrap()
+    }
+
+    /// Runs the machine second by second for `duration` like the module does, returning the
+    /// minutes since the start of the test at which each event happened.
+    async fn run(
+        machine: &mut ClockMachine<'_>,
+        backend: &dyn ClockBackend,
+        time: &FakeTime,
+        start: DateTime<Local>,
+        duration: Duration,
+    ) -> Vec<(i64, Event)> {
+        let end = time.now() + duration;
+        let mut events = vec![];
+
+        while time.now() < end {
+            time.advance(Duration::seconds(1));
+            let now = time.now();
+            machine.set_clock(backend.clock().await.unwrap(), now);
+            events.extend(
+                machine
+                    .tick(now)
+                    .into_iter()
+                    .map(|event| ((now - start).num_minutes(), event)),
+            );
+        }
+
+        events
+    }
+
+    fn break_times(events: &[(i64, Event)]) -> Vec<i64> {
+        events
+            .iter()
+            .filter(|(_, event)| matches!(event, Event::BreakDue { .. }))
+            .map(|(minute, _)| *minute)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn notifies_at_notify_time_and_every_interval() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let events = run(&mut machine, &backend, &time, start, Duration::minutes(75)).await;
+
+        assert_eq!(break_times(&events), vec![50, 60, 70]);
+    }
+
+    #[tokio::test]
+    async fn notifies_once_without_interval() {
+        let mut config = config();
+        config.notify_interval = 0;
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let events = run(&mut machine, &backend, &time, start, Duration::minutes(120)).await;
+
+        assert_eq!(break_times(&events), vec![50]);
+    }
+
+    #[tokio::test]
+    async fn stops_notifying_after_clock_out() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(55)).await;
+        backend.clock_out(None).await.unwrap();
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(60)).await);
+
+        assert_eq!(break_times(&events), vec![50]);
+    }
+
+    #[tokio::test]
+    async fn snooze_puts_off_the_next_notification() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(51)).await;
+        machine.snooze(time.now() + Duration::minutes(25));
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(40)).await);
+
+        // Dismissing leaves the schedule alone, the snoozed one resumes the interval from there
+        assert_eq!(break_times(&events), vec![50, 76, 80, 90]);
+    }
+
+    #[tokio::test]
+    async fn keeps_schedule_when_effort_or_clocked_time_changes() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(52)).await;
+        if let Some(clock) = backend.clock.borrow_mut().as_mut() {
+            clock.effort = Some(Duration::hours(2));
+            clock.clocked_before = Duration::minutes(30);
+        }
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(10)).await);
+
+        assert_eq!(break_times(&events), vec![50, 60]);
+    }
+
+    #[tokio::test]
+    async fn reschedules_when_task_changes() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(30)).await;
+        backend.clock_in("Review").await.unwrap();
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(55)).await);
+
+        assert_eq!(break_times(&events), vec![80]);
+    }
+
+    #[tokio::test]
+    async fn skips_missed_notifications_after_suspend() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(45)).await;
+        time.advance(Duration::minutes(22));
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(15)).await);
+
+        assert_eq!(break_times(&events), vec![70, 80]);
+    }
+
+    #[tokio::test]
+    async fn notifies_right_away_for_a_long_running_clock() {
+        let config = config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        time.advance(Duration::minutes(55));
+        let events = run(&mut machine, &backend, &time, start, Duration::minutes(10)).await;
+
+        assert_eq!(break_times(&events), vec![55, 60]);
+    }
+
+    #[tokio::test]
+    async fn offers_to_discard_idle_time_of_running_clock() {
+        let mut config = config();
+        config.idle_time = Some(10);
+        let time = FakeTime::new();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), time.now());
+
+        backend.clock_in("Write report").await.unwrap();
+        time.advance(Duration::minutes(5));
+        let idle_since = time.now();
+        machine.set_clock(backend.clock().await.unwrap(), time.now());
+        assert_eq!(machine.set_idle(Some(idle_since), time.now()), None);
+
+        time.advance(Duration::minutes(15));
+        assert_eq!(
+            machine.set_idle(None, time.now()),
+            Some(Event::IdleReturned { since: idle_since })
+        );
+    }
+
+    #[tokio::test]
+    async fn forgets_idle_time_when_clock_stops() {
+        let mut config = config();
+        config.idle_time = Some(10);
+        let time = FakeTime::new();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), time.now());
+
+        backend.clock_in("Write report").await.unwrap();
+        machine.set_clock(backend.clock().await.unwrap(), time.now());
+        machine.set_idle(Some(time.now()), time.now());
+
+        time.advance(Duration::minutes(5));
+        backend.clock_out(None).await.unwrap();
+        machine.set_clock(backend.clock().await.unwrap(), time.now());
+        machine.set_idle(Some(time.now()), time.now());
+
+        time.advance(Duration::minutes(10));
+        assert_eq!(machine.set_idle(None, time.now()), None);
+    }
+
+    #[tokio::test]
+    async fn ignores_idle_time_when_clock_restarts() {
+        let mut config = config();
+        config.idle_time = Some(10);
+        let time = FakeTime::new();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), time.now());
+
+        backend.clock_in("Write report").await.unwrap();
+        machine.set_clock(backend.clock().await.unwrap(), time.now());
+        machine.set_idle(Some(time.now()), time.now());
+
+        // Clocked out and in again between two polls
+        time.advance(Duration::minutes(15));
+        backend.clock_in("Review").await.unwrap();
+        machine.set_clock(backend.clock().await.unwrap(), time.now());
+        assert_eq!(machine.set_idle(None, time.now()), None);
+    }
+
+    fn pomodoro_config() -> TimeTrackingConfig {
+        let mut config = config();
+        config.pomodoro = PomodoroConfig {
+            enabled: true,
+            long_break_after: 2,
+            ..PomodoroConfig::default()
+        };
+        config
+    }
+
+    fn phase(machine: &ClockMachine) -> Phase {
+        machine.pomodoro().unwrap().0.phase
+    }
+
+    fn short_break() -> Event {
+        Event::BreakStarted {
+            phase: Phase::ShortBreak,
+            length: Duration::minutes(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn alternates_work_and_short_breaks() {
+        let config = pomodoro_config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let events = run(&mut machine, &backend, &time, start, Duration::minutes(31)).await;
+
+        assert_eq!(
+            events,
+            [
+                (25, short_break()),
+                (30, Event::BreakOver { clocked_in: true }),
+            ]
+        );
+        assert_eq!(phase(&machine), Phase::Work);
+    }
+
+    #[tokio::test]
+    async fn takes_a_long_break_after_long_break_after_work_periods() {
+        let config = pomodoro_config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let events = run(&mut machine, &backend, &time, start, Duration::minutes(85)).await;
+
+        assert_eq!(
+            events,
+            [
+                (25, short_break()),
+                (30, Event::BreakOver { clocked_in: true }),
+                (
+                    55,
+                    Event::BreakStarted {
+                        phase: Phase::LongBreak,
+                        length: Duration::minutes(15),
+                    }
+                ),
+                (70, Event::BreakOver { clocked_in: true }),
+            ]
+        );
+        // The count starts over after the long break
+        assert_eq!(machine.pomodoro().unwrap().0.completed, 0);
+    }
+
+    #[tokio::test]
+    async fn stops_work_period_on_clock_out() {
+        let config = pomodoro_config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(10)).await;
+        backend.clock_out(None).await.unwrap();
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(30)).await);
+        assert_eq!(phase(&machine), Phase::Idle);
+
+        // Clocking in again starts a whole new work period
+        backend.clock_in("Write report").await.unwrap();
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(26)).await);
+
+        assert_eq!(events, [(65, short_break())]);
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_clock_after_a_break_without_one() {
+        let config = pomodoro_config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(26)).await;
+        // As with `clock_out` set in the pomodoro configuration
+        backend.clock_out(None).await.unwrap();
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(10)).await);
+
+        assert_eq!(
+            events,
+            [
+                (25, short_break()),
+                (30, Event::BreakOver { clocked_in: false }),
+            ]
+        );
+        assert_eq!(phase(&machine), Phase::Idle);
+    }
+
+    #[tokio::test]
+    async fn starts_a_new_work_period_after_suspend() {
+        let config = pomodoro_config();
+        let time = FakeTime::new();
+        let start = time.now();
+        let backend = FakeBackend::new(&time);
+        let mut machine = ClockMachine::new(Some(&config), start);
+
+        backend.clock_in("Write report").await.unwrap();
+        let mut events = run(&mut machine, &backend, &time, start, Duration::minutes(20)).await;
+        time.advance(Duration::minutes(40));
+        events.extend(run(&mut machine, &backend, &time, start, Duration::minutes(30)).await);
+
+        // The work period that ran out while suspended neither starts a break nor counts
+        assert_eq!(events, [(85, short_break())]);
+        assert_eq!(machine.pomodoro().unwrap().0.completed, 1);
+    }
+
+    #[test]
+    fn keeps_progress_of_a_clock_started_within_the_work_period() {
+        let config = PomodoroConfig::default();
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        let clock = |started_ago| Clock {
+            task: String::from("Write report"),
+            start: now - started_ago,
+            effort: None,
+            clocked_before: Duration::zero(),
+        };
+
+        let recent = Pomodoro::new(now).advance(&config, Some(&clock(Duration::minutes(10))), now);
+        let old = Pomodoro::new(now).advance(&config, Some(&clock(Duration::hours(2))), now);
+
+        assert_eq!(recent.phase, Phase::Work);
+        assert_eq!(recent.remaining(&config, now), Duration::minutes(15));
+        assert_eq!(old.remaining(&config, now), Duration::minutes(25));
+    }
+
+    #[test]
+    fn notify_delay_follows_interval() {
+        let config = config();
+
+        assert_eq!(
+            notify_delay(&config, Duration::minutes(20)),
+            Some(Duration::minutes(30))
+        );
+        assert_eq!(
+            notify_delay(&config, Duration::minutes(50)),
+            Some(Duration::minutes(10))
+        );
+        assert_eq!(
+            notify_delay(&config, Duration::minutes(63)),
+            Some(Duration::minutes(7))
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+use serde::Deserialize;
+use tokio::process::Command;
+
+use super::{Clock, ClockBackend};
+use crate::org::ClockEntry;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// How far back to look for recently tracked tasks
+const RECENT_DAYS: i64 = 14;
+
+/// Tags of an interval are joined into one task name, and split again to start tracking it
+const TAG_SEPARATOR: &str = ", ";
+
+/// An interval as printed by `timew export`
+#[derive(Deserialize)]
+struct Interval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl Interval {
+    fn task(&self) -> String {
+        self.tags.join(TAG_SEPARATOR)
+    }
+}
+
+/// Timewarrior, where the tags of an interval make up the task.
+pub struct TimewarriorBackend;
+
+impl TimewarriorBackend {
+    async fn export(&self, since: NaiveDateTime) -> Result<Vec<Interval>, BoxedError> {
+        let output = Command::new("timew")
+            .arg("export")
+            .arg("from")
+            .arg(since.format("%Y-%m-%dT%H:%M:%S").to_string())
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<(), BoxedError> {
+        let output = Command::new("timew").args(args).output().await?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl ClockBackend for TimewarriorBackend {
+    async fn clock(&self) -> Result<Option<Clock>, BoxedError> {
+        let output = Command::new("timew")
+            .args(["get", "dom.active"])
+            .output()
+            .await?;
+        if String::from_utf8(output.stdout)?.trim() != "1" {
+            return Ok(None);
+        }
+
+        let output = Command::new("timew")
+            .args(["get", "dom.active.json"])
+            .output()
+            .await?;
+        let interval: Interval = serde_json::from_slice(&output.stdout)?;
+
+        Ok(Some(Clock {
+            task: interval.task(),
+            start: parse_timestamp(&interval.start)?,
+            effort: None,
+            clocked_before: Duration::zero(),
+        }))
+    }
+
+    async fn entries(&self, since: NaiveDateTime) -> Result<Vec<ClockEntry>, BoxedError> {
+        clock_entries(&self.export(since).await?)
+    }
+
+    async fn recent_tasks(&self) -> Result<Vec<String>, BoxedError> {
+        let since = Local::now().naive_local() - Duration::days(RECENT_DAYS);
+
+        let mut tasks = vec![];
+        for interval in self.export(since).await?.iter().rev() {
+            let task = interval.task();
+            if !task.is_empty() && !tasks.contains(&task) {
+                tasks.push(task);
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    async fn clock_in(&self, task: &str) -> Result<(), BoxedError> {
+        let mut args = vec!["start"];
+        args.extend(task.split(TAG_SEPARATOR));
+        self.run(&args).await
+    }
+
+    async fn clock_in_last(&self) -> Result<(), BoxedError> {
+        self.run(&["continue"]).await
+    }
+
+    async fn clock_out(&self, at: Option<DateTime<Local>>) -> Result<(), BoxedError> {
+        match at {
+            Some(at) => {
+                self.run(&["stop", &at.format("%Y-%m-%dT%H:%M:%S").to_string()])
+                    .await
+            }
+            None => self.run(&["stop"]).await,
+        }
+    }
+}
+
+/// Turns the finished intervals into clock entries, leaving out the one still being tracked.
+fn clock_entries(intervals: &[Interval]) -> Result<Vec<ClockEntry>, BoxedError> {
+    let mut entries = vec![];
+    for interval in intervals {
+        let Some(end) = &interval.end else {
+            continue;
+        };
+
+        entries.push(ClockEntry {
+            heading: interval.task(),
+            start: parse_timestamp(&interval.start)?.naive_local(),
+            end: Some(parse_timestamp(end)?.naive_local()),
+            effort: None,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Parses the UTC timestamps of Timewarrior, like `20240501T091200Z`.
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Local>, BoxedError> {
+    let utc = NaiveDateTime::parse_from_str(timestamp, "%Y%m%dT%H%M%SZ")?.and_utc();
+    Ok(utc.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn parses_utc_timestamps() {
+        assert_eq!(
+            parse_timestamp("20240501T071200Z").unwrap(),
+            utc(5, 1, 7, 12)
+        );
+        assert!(parse_timestamp("2024-05-01T07:12:00Z").is_err());
+        assert!(parse_timestamp("20240501T071200").is_err());
+    }
+
+    #[test]
+    fn reads_finished_intervals_of_an_export() {
+        let intervals: Vec<Interval> =
+            serde_json::from_slice(include_bytes!("../../../tests/fixtures/timew/export.json"))
+                .unwrap();
+
+        assert_eq!(
+            clock_entries(&intervals).unwrap(),
+            [
+                ClockEntry {
+                    heading: String::from("Deploy"),
+                    start: utc(4, 30, 21, 30).naive_local(),
+                    end: Some(utc(4, 30, 22, 30).naive_local()),
+                    effort: None,
+                },
+                ClockEntry {
+                    heading: String::from("work, Write report"),
+                    start: utc(5, 1, 7, 12).naive_local(),
+                    end: Some(utc(5, 1, 8, 30).naive_local()),
+                    effort: None,
+                },
+            ]
+        );
+        // The running interval has no end but still names its task
+        assert_eq!(intervals[2].task(), "Review pull request");
+        assert_eq!(intervals[2].end, None);
+    }
+}
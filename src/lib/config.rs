@@ -1,20 +1,38 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+use crate::emacs::EmacsConnection;
+
+/// The sections of `modules.toml`. Each module only needs its own, so the ones without defaults
+/// are optional and checked by the module that uses them.
+#[derive(Deserialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
-    pub general: GeneralConfig,
-    pub openweathermap: OpenWeatherMapConfig,
-    pub org_clock: OrgClockConfig,
-    pub unfinished_projects: UnfinishedProjectsConfig,
+    pub general: Option<GeneralConfig>,
+    pub openweathermap: Option<OpenWeatherMapConfig>,
+    /// Formerly the `org-clock` section, which is still read
+    #[serde(alias = "org-clock")]
+    pub time_tracking: Option<TimeTrackingConfig>,
+    pub unfinished_projects: Option<UnfinishedProjectsConfig>,
+    /// Also read as `forge-notifications`, as it covers GitLab and Gitea too
+    #[serde(default, alias = "forge-notifications")]
+    pub github_notifications: GithubNotificationsConfig,
+    #[serde(default)]
+    pub github_review_queue: GithubReviewQueueConfig,
+    #[serde(default)]
+    pub mako: MakoConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
 }
 
 impl Config {
     pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
         let data = String::from_utf8(std::fs::read(path)?)?;
-        let config: Config = toml::from_str(&data)?;
+        let config: Config = toml::from_str(&data)
+            .map_err(|e| format!("Could not parse {}: {e}", path.display()))?;
 
         Ok(config)
     }
@@ -27,6 +45,22 @@ impl Config {
 
         Self::from_file(&config_path)
     }
+
+    /// Like [`Config::load`], but without a configuration file every section has its defaults.
+    /// A file that cannot be read or parsed is still an error.
+    pub fn load_or_default(
+        app_name: &str,
+        file_name: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let config_path = xdg::BaseDirectories::with_prefix(app_name)
+            .or(Err("Could not load app configuration"))?
+            .find_config_file(file_name);
+
+        match config_path {
+            Some(config_path) => Self::from_file(&config_path),
+            None => Ok(Self::default()),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -41,10 +75,145 @@ pub struct OpenWeatherMapConfig {
 }
 
 #[derive(Deserialize)]
-pub struct OrgClockConfig {
+pub struct TimeTrackingConfig {
     pub notify_time: u32,
     pub notify_interval: u32,
+    /// Minutes on one clock until the module shows `alert`, and `critical` after twice as long
     pub alert_time: u32,
+    #[serde(default)]
+    pub backend: TimeTrackingBackend,
+    #[serde(default)]
+    pub connection: EmacsConnection,
+    /// Emacs server socket, defaults to the one `emacsclient` would use
+    pub socket: Option<PathBuf>,
+    #[serde(default)]
+    pub source: ClockSource,
+    /// Org files, or directories of them, to read the clock from when not asking Emacs
+    #[serde(default)]
+    pub agenda_files: Vec<String>,
+    /// File for the `file` backend, defaults to `time-tracking.tsv` in the XDG data directory
+    pub file: Option<PathBuf>,
+    /// Minutes to clock each day, shown as the progress of the module
+    pub daily_target: Option<u32>,
+    /// Command that picks one of the lines on its input, like `fuzzel --dmenu`, used to switch
+    /// tasks. Defaults to `rofi -dmenu`.
+    pub menu_command: Option<String>,
+    #[serde(default)]
+    pub pomodoro: PomodoroConfig,
+    /// Minutes the session has to be idle, as reported to logind, before offering to discard
+    /// the time from the running clock
+    pub idle_time: Option<u32>,
+    /// When a clock is expected to run, no reminders are sent without it
+    pub work_hours: Option<WorkHoursConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WorkHoursConfig {
+    /// Days like `mon` or `monday`
+    pub days: Vec<String>,
+    /// Time ranges like `09:00-17:00`, or `22:00-06:00` for one past midnight
+    pub hours: Vec<String>,
+    /// Minutes without a running clock until the module warns
+    pub untracked_time: u32,
+}
+
+impl WorkHoursConfig {
+    /// Finds the start of the work period `time` falls in. A range that ends before it starts,
+    /// like `22:00-06:00`, runs past midnight and belongs to the day it starts on. Days and
+    /// ranges that cannot be parsed are ignored.
+    pub fn period_start(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.hours
+            .iter()
+            .filter_map(|range| {
+                let (start, end) = range.split_once('-')?;
+                let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+                let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+
+                let start_date = if start < end {
+                    (start..end).contains(&time.time()).then_some(time.date())?
+                } else if time.time() >= start {
+                    time.date()
+                } else if time.time() < end {
+                    time.date().pred_opt()?
+                } else {
+                    return None;
+                };
+
+                self.is_work_day(start_date)
+                    .then(|| start_date.and_time(start))
+            })
+            .min()
+    }
+
+    fn is_work_day(&self, date: NaiveDate) -> bool {
+        self.days.iter().any(|day| {
+            day.parse::<Weekday>()
+                .is_ok_and(|day| day == date.weekday())
+        })
+    }
+}
+
+impl Default for WorkHoursConfig {
+    fn default() -> Self {
+        Self {
+            days: ["mon", "tue", "wed", "thu", "fri"]
+                .map(String::from)
+                .to_vec(),
+            hours: vec![String::from("09:00-17:00")],
+            untracked_time: 15,
+        }
+    }
+}
+
+/// Work and break lengths in minutes. When enabled, the breaks replace the notifications every
+/// `notify_interval` minutes.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PomodoroConfig {
+    pub enabled: bool,
+    pub work: u32,
+    pub short_break: u32,
+    pub long_break: u32,
+    /// Number of work periods until a long break
+    pub long_break_after: u32,
+    /// Clock out when a break starts
+    pub clock_out: bool,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            work: 25,
+            short_break: 5,
+            long_break: 15,
+            long_break_after: 4,
+            clock_out: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeTrackingBackend {
+    /// The org clock in Emacs
+    #[default]
+    Org,
+    /// `timew`
+    Timewarrior,
+    /// A plain text file kept by the module itself
+    File,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockSource {
+    /// Ask Emacs, reading the agenda files only when Emacs cannot be reached
+    #[default]
+    Emacs,
+    /// Only read the agenda files
+    Files,
 }
 
 #[derive(Deserialize)]
@@ -56,3 +225,247 @@ pub struct UnfinishedProjectsConfig {
     pub warning_age: u64,
     pub critical_age: u64,
 }
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct GithubNotificationsConfig {
+    /// Notification reasons, such as `review_requested` or `mention`, that raise a desktop
+    /// notification when they arrive
+    pub notify_reasons: Vec<String>,
+    /// Token to use when `GITHUB_TOKEN` and `GH_TOKEN` are not set
+    pub token: Option<String>,
+    /// Shell command that prints the token, e.g. to read it from a password manager
+    pub token_command: Option<String>,
+    /// Accounts to fetch notifications for, when empty only github.com is used with the token
+    /// settings above
+    pub accounts: Vec<ForgeAccountConfig>,
+}
+
+impl GithubNotificationsConfig {
+    pub fn accounts(&self) -> Vec<ForgeAccountConfig> {
+        if !self.accounts.is_empty() {
+            return self.accounts.clone();
+        }
+
+        vec![ForgeAccountConfig {
+            token: self.token.clone(),
+            token_command: self.token_command.clone(),
+            ..Default::default()
+        }]
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    #[default]
+    Github,
+    Gitlab,
+    #[serde(alias = "forgejo")]
+    Gitea,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ForgeAccountConfig {
+    #[serde(default)]
+    pub forge: Forge,
+    /// Name used in the tooltip, defaults to the host
+    pub name: Option<String>,
+    #[serde(default = "default_github_host")]
+    pub host: String,
+    /// API endpoint, defaults to `https://api.github.com` for github.com,
+    /// `https://<host>/api/v3` for GitHub Enterprise Server, `https://<host>/api/v4` for GitLab
+    /// and `https://<host>/api/v1` for Gitea and Forgejo
+    pub base_url: Option<String>,
+    pub token: Option<String>,
+    pub token_command: Option<String>,
+}
+
+impl ForgeAccountConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.host)
+    }
+
+    pub fn base_url(&self) -> String {
+        match (&self.base_url, self.forge) {
+            (Some(base_url), _) => base_url.trim_end_matches('/').to_owned(),
+            (None, Forge::Github) if self.host == GITHUB_HOST => {
+                String::from("https://api.github.com")
+            }
+            (None, Forge::Github) => format!("https://{}/api/v3", self.host),
+            (None, Forge::Gitlab) => format!("https://{}/api/v4", self.host),
+            (None, Forge::Gitea) => format!("https://{}/api/v1", self.host),
+        }
+    }
+}
+
+impl Default for ForgeAccountConfig {
+    fn default() -> Self {
+        Self {
+            forge: Forge::Github,
+            name: None,
+            host: default_github_host(),
+            base_url: None,
+            token: None,
+            token_command: None,
+        }
+    }
+}
+
+const GITHUB_HOST: &str = "github.com";
+
+fn default_github_host() -> String {
+    String::from(GITHUB_HOST)
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GithubReviewQueueConfig {
+    pub account: ForgeAccountConfig,
+    /// Search for pull requests waiting for your review
+    pub review_query: String,
+    /// Search for your own pull requests, of which the failing ones are counted
+    pub authored_query: String,
+    /// Hours a review may wait before the module turns to warning
+    pub review_warning_age: u64,
+    /// Seconds between searches
+    pub interval: u64,
+}
+
+impl Default for GithubReviewQueueConfig {
+    fn default() -> Self {
+        Self {
+            account: ForgeAccountConfig::default(),
+            review_query: String::from("is:open is:pr archived:false review-requested:@me"),
+            authored_query: String::from("is:open is:pr archived:false author:@me"),
+            review_warning_age: 24,
+            interval: 300,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MakoConfig {
+    /// Mako mode that hides notifications, as set up in the mako configuration
+    pub dnd_mode: String,
+    /// Number of dismissed notifications to show in the tooltip
+    pub history_size: usize,
+    /// Number of characters of the body to show under each notification
+    pub body_length: usize,
+}
+
+impl Default for MakoConfig {
+    fn default() -> Self {
+        Self {
+            dnd_mode: String::from("do-not-disturb"),
+            history_size: 5,
+            body_length: 80,
+        }
+    }
+}
+
+/// Takes the same settings as the `mako` section, of which `dnd_mode` only applies to mako
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    pub daemon: NotificationDaemonKind,
+    #[serde(flatten)]
+    pub mako: MakoConfig,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationDaemonKind {
+    /// Whichever daemon owns the notifications name on the session bus
+    #[default]
+    Auto,
+    Mako,
+    Dunst,
+    Swaync,
+}
+
+impl FromStr for NotificationDaemonKind {
+    type Err = String;
+
+    /// Parses the name a daemon reports through `GetServerInformation`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "mako" => Ok(Self::Mako),
+            "dunst" => Ok(Self::Dunst),
+            "SwayNotificationCenter" | "swaync" => Ok(Self::Swaync),
+            name => Err(format!("Unsupported notification daemon {name}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn work_hours(hours: &[&str]) -> WorkHoursConfig {
+        WorkHoursConfig {
+            hours: hours.iter().map(|range| range.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // May 2024 starts on a Wednesday
+        NaiveDate::from_ymd_opt(2024, 5, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_period_within_a_day() {
+        let config = work_hours(&["09:00-12:00", "13:00-17:00"]);
+
+        assert_eq!(config.period_start(time(1, 10, 0)), Some(time(1, 9, 0)));
+        assert_eq!(config.period_start(time(1, 13, 0)), Some(time(1, 13, 0)));
+        assert_eq!(config.period_start(time(1, 12, 30)), None);
+        assert_eq!(config.period_start(time(1, 17, 0)), None);
+        // Saturday
+        assert_eq!(config.period_start(time(4, 10, 0)), None);
+    }
+
+    #[test]
+    fn finds_period_past_midnight() {
+        let config = work_hours(&["22:00-06:00"]);
+
+        assert_eq!(config.period_start(time(1, 23, 0)), Some(time(1, 22, 0)));
+        assert_eq!(config.period_start(time(2, 5, 59)), Some(time(1, 22, 0)));
+        assert_eq!(config.period_start(time(2, 6, 0)), None);
+        assert_eq!(config.period_start(time(2, 21, 59)), None);
+        // The Friday night shift runs into Saturday, but none starts on Saturday night
+        assert_eq!(config.period_start(time(4, 3, 0)), Some(time(3, 22, 0)));
+        assert_eq!(config.period_start(time(4, 23, 0)), None);
+        // Nor does the Sunday night shift belong to Monday
+        assert_eq!(config.period_start(time(6, 3, 0)), None);
+    }
+
+    #[test]
+    fn reads_a_file_with_only_one_section() {
+        let config: Config = toml::from_str(
+            r#"
+            [github-notifications]
+            accounts = [
+                { forge = "gitlab", host = "gitlab.example.com" },
+                { forge = "gitea", host = "codeberg.org" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let hosts = config
+            .github_notifications
+            .accounts()
+            .into_iter()
+            .map(|account| account.host)
+            .collect::<Vec<_>>();
+        assert_eq!(hosts, vec!["gitlab.example.com", "codeberg.org"]);
+        assert!(config.general.is_none());
+        assert!(config.time_tracking.is_none());
+    }
+}
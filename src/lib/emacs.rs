@@ -0,0 +1,281 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// How to reach a running Emacs server
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EmacsConnection {
+    /// Spawn `emacsclient --eval`
+    #[default]
+    Emacsclient,
+    /// Speak the server protocol over the Unix socket directly, which avoids spawning a process
+    Socket,
+}
+
+pub struct Emacs {
+    connection: EmacsConnection,
+    socket: Option<PathBuf>,
+}
+
+impl Emacs {
+    pub fn new(connection: EmacsConnection, socket: Option<PathBuf>) -> Self {
+        Self { connection, socket }
+    }
+
+    /// Evaluates an expression and returns the printed result.
+    pub async fn eval(&self, expression: &str) -> Result<String, BoxedError> {
+        match self.connection {
+            EmacsConnection::Emacsclient => self.eval_emacsclient(expression).await,
+            EmacsConnection::Socket => self.eval_socket(expression).await,
+        }
+    }
+
+    async fn eval_emacsclient(&self, expression: &str) -> Result<String, BoxedError> {
+        let mut command = tokio::process::Command::new("emacsclient");
+        if let Some(socket) = &self.socket {
+            command.arg("--socket-name").arg(socket);
+        }
+
+        let output = command.arg("--eval").arg(expression).output().await?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .to_owned()
+                .into());
+        }
+
+        Ok(String::from_utf8(output.stdout)?.trim_end().to_string())
+    }
+
+    /// Sends `-eval` to the server and collects the `-print` and `-print-nonl` replies, as
+    /// `emacsclient` would.
+    async fn eval_socket(&self, expression: &str) -> Result<String, BoxedError> {
+        let socket = match &self.socket {
+            Some(socket) => socket.clone(),
+            None => default_socket().ok_or("Could not find the Emacs server socket")?,
+        };
+
+        let mut stream = UnixStream::connect(socket).await?;
+        stream
+            .write_all(format!("-eval {}\n", quote_argument(expression)).as_bytes())
+            .await?;
+
+        let mut result = String::new();
+        let mut lines = BufReader::new(stream).lines();
+        while let Some(line) = lines.next_line().await? {
+            let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+            match command {
+                "-print" | "-print-nonl" => result.push_str(&unquote_argument(argument)),
+                "-error" => return Err(unquote_argument(argument).into()),
+                _ => {}
+            }
+        }
+
+        Ok(result.trim_end().to_string())
+    }
+}
+
+/// Finds the socket of the default server the way `emacsclient` does.
+fn default_socket() -> Option<PathBuf> {
+    let name = std::env::var("EMACS_SOCKET_NAME").unwrap_or_else(|_| String::from("server"));
+    if name.contains('/') {
+        return Some(PathBuf::from(name));
+    }
+
+    let runtime_socket = std::env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .map(|runtime_dir| PathBuf::from(runtime_dir).join("emacs").join(&name));
+    let tmp_socket = std::env::var("UID")
+        .ok()
+        .or_else(current_uid)
+        .map(|uid| PathBuf::from(format!("/tmp/emacs{uid}")).join(&name));
+
+    runtime_socket
+        .into_iter()
+        .chain(tmp_socket)
+        .find(|socket| socket.exists())
+}
+
+fn current_uid() -> Option<String> {
+    let output = std::process::Command::new("id").arg("-u").output().ok()?;
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+/// Quotes an argument for the server protocol, where spaces, newlines and leading dashes are
+/// escaped with `&`.
+fn quote_argument(argument: &str) -> String {
+    let mut quoted = String::with_capacity(argument.len());
+    for (i, c) in argument.chars().enumerate() {
+        match c {
+            '&' => quoted.push_str("&&"),
+            ' ' => quoted.push_str("&_"),
+            '\n' => quoted.push_str("&n"),
+            '-' if i == 0 => quoted.push_str("&-"),
+            c => quoted.push(c),
+        }
+    }
+    quoted
+}
+
+fn unquote_argument(argument: &str) -> String {
+    let mut unquoted = String::with_capacity(argument.len());
+    let mut chars = argument.chars();
+    while let Some(c) = chars.next() {
+        if c != '&' {
+            unquoted.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('_') => unquoted.push(' '),
+            Some('n') => unquoted.push('\n'),
+            Some(c) => unquoted.push(c),
+            None => {}
+        }
+    }
+    unquoted
+}
+
+/// Quotes text as an elisp string literal.
+pub fn quote_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reads a string as printed by Emacs, e.g. `"a \"quoted\" word"`.
+pub fn read_string(printed: &str) -> Result<String, BoxedError> {
+    let inner = printed
+        .strip_prefix('"')
+        .and_then(|x| x.strip_suffix('"'))
+        .ok_or("Not a printed string")?;
+
+    let mut string = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(c) => string.push(c),
+                None => {}
+            },
+            c => string.push(c),
+        }
+    }
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    #[test]
+    fn quotes_special_characters() {
+        assert_eq!(
+            quote_argument("-a b&c\nd-e"),
+            String::from("&-a&_b&&c&nd-e")
+        );
+    }
+
+    #[test]
+    fn unquotes_what_was_quoted() {
+        for argument in [
+            "(org-clock-in)",
+            "-leading dash",
+            "a & b && c",
+            "two\nlines\n",
+            "&_ looks quoted",
+            "",
+        ] {
+            assert_eq!(unquote_argument(&quote_argument(argument)), argument);
+        }
+    }
+
+    #[test]
+    fn reads_printed_strings() {
+        assert_eq!(
+            read_string(r#""a \"quoted\" word""#).unwrap(),
+            "a \"quoted\" word"
+        );
+        assert_eq!(
+            read_string(r#""one\ntwo\tthree""#).unwrap(),
+            "one\ntwo\tthree"
+        );
+        assert_eq!(read_string(r#""back\\slash""#).unwrap(), "back\\slash");
+        assert!(read_string("nil").is_err());
+    }
+
+    #[test]
+    fn reads_quoted_strings() {
+        let text = "Write \"report\" \\ review";
+        assert_eq!(read_string(&quote_string(text)).unwrap(), text);
+    }
+
+    /// Starts a server on a fresh socket that answers the first request with `reply`, and
+    /// returns the socket together with the request it received.
+    async fn fake_server(
+        name: &str,
+        reply: &'static str,
+    ) -> (PathBuf, tokio::task::JoinHandle<String>) {
+        let socket =
+            std::env::temp_dir().join(format!("waybar-extensions-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let request = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let request = BufReader::new(reader)
+                .lines()
+                .next_line()
+                .await
+                .unwrap()
+                .unwrap();
+            writer.write_all(reply.as_bytes()).await.unwrap();
+            request
+        });
+
+        (socket, request)
+    }
+
+    #[tokio::test]
+    async fn joins_replies_over_several_lines() {
+        let (socket, request) = fake_server(
+            "print",
+            "-emacs-pid 4242\n-print \"Write&_report&nfirst\n-print-nonl &_draft\"\n",
+        )
+        .await;
+
+        let emacs = Emacs::new(EmacsConnection::Socket, Some(socket.clone()));
+        let result = emacs.eval("(org-clock-heading)").await.unwrap();
+        std::fs::remove_file(socket).unwrap();
+
+        assert_eq!(request.await.unwrap(), "-eval (org-clock-heading)");
+        assert_eq!(result, "\"Write report\nfirst draft\"");
+    }
+
+    #[tokio::test]
+    async fn reports_errors_from_the_server() {
+        let (socket, request) = fake_server(
+            "error",
+            "-emacs-pid 4242\n-error Symbol’s&_value&_as&_variable&_is&_void:&_org-clock-heading\n",
+        )
+        .await;
+
+        let emacs = Emacs::new(EmacsConnection::Socket, Some(socket.clone()));
+        let error = emacs.eval("org-clock-heading").await.unwrap_err();
+        std::fs::remove_file(socket).unwrap();
+
+        assert_eq!(request.await.unwrap(), "-eval org-clock-heading");
+        assert_eq!(
+            error.to_string(),
+            "Symbol’s value as variable is void: org-clock-heading"
+        );
+    }
+}
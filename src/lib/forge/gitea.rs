@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{BoxedError, ForgeNotification, Provider};
+use crate::config::{Forge, ForgeAccountConfig};
+
+const PAGE_SIZE: usize = 50;
+
+/// Limit on the pages to read, in case a server ignores `page` and sends no total count
+const MAX_PAGES: usize = 100;
+
+/// Unread Gitea and Forgejo notifications, which share the same API
+pub struct GiteaProvider {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Thread {
+    id: u64,
+    repository: Repository,
+    subject: Subject,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Repository {
+    full_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Subject {
+    title: String,
+    html_url: Option<Url>,
+    r#type: String,
+}
+
+impl GiteaProvider {
+    pub fn new(config: &ForgeAccountConfig, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: config.base_url(),
+            token,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Provider for GiteaProvider {
+    async fn notifications(&mut self) -> Result<Vec<ForgeNotification>, BoxedError> {
+        let mut threads: Vec<Thread> = vec![];
+        let mut previous_ids = vec![];
+
+        for page in 1..=MAX_PAGES {
+            let response = self
+                .client
+                .get(format!("{}/notifications", self.base_url))
+                .header("Authorization", format!("token {}", self.token))
+                .query(&[
+                    ("status-types", "unread"),
+                    ("limit", &PAGE_SIZE.to_string()),
+                    ("page", &page.to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?;
+
+            let total_count = response
+                .headers()
+                .get("x-total-count")
+                .and_then(|x| x.to_str().ok())
+                .and_then(|x| x.parse::<usize>().ok());
+            let mut page_threads: Vec<Thread> = response.json().await?;
+
+            // A server that ignores `page` sends the first page again
+            let ids = page_threads
+                .iter()
+                .map(|thread| thread.id)
+                .collect::<Vec<_>>();
+            if !ids.is_empty() && ids == previous_ids {
+                break;
+            }
+            previous_ids = ids;
+
+            // Servers may return fewer items per page than asked for, when `MAX_RESPONSE_ITEMS`
+            // is set lower, so only the total or an empty page tell that this was the last one
+            let last_page = page_threads.is_empty()
+                || total_count.is_some_and(|total| threads.len() + page_threads.len() >= total);
+            threads.append(&mut page_threads);
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(threads.into_iter().map(Into::into).collect())
+    }
+
+    async fn mark_read(&self, repo: Option<(&str, &str)>) -> Result<(), BoxedError> {
+        let route = match repo {
+            None => String::from("/notifications"),
+            Some((owner, repo)) => format!("/repos/{owner}/{repo}/notifications"),
+        };
+
+        self.client
+            .put(format!("{}{route}", self.base_url))
+            .header("Authorization", format!("token {}", self.token))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl From<Thread> for ForgeNotification {
+    fn from(thread: Thread) -> Self {
+        let kind = match thread.subject.r#type.as_str() {
+            "Pull" => "PullRequest",
+            "Repository" => "Release",
+            subject_type => subject_type,
+        };
+
+        ForgeNotification {
+            id: thread.id.to_string(),
+            forge: Forge::Gitea,
+            repository: thread.repository.full_name,
+            title: thread.subject.title,
+            kind: kind.to_owned(),
+            // Gitea does not tell why a notification was sent
+            reason: String::new(),
+            updated_at: thread.updated_at,
+            url: thread.subject.html_url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn thread(id: u64) -> Value {
+        json!({
+            "id": id,
+            "repository": { "full_name": "owner/repo" },
+            "subject": {
+                "title": format!("Notification {id}"),
+                "html_url": format!("https://codeberg.org/owner/repo/issues/{id}"),
+                "type": "Issue"
+            },
+            "updated_at": "2024-05-01T09:00:00Z"
+        })
+    }
+
+    fn provider(server: &MockServer) -> GiteaProvider {
+        GiteaProvider::new(
+            &ForgeAccountConfig {
+                forge: Forge::Gitea,
+                base_url: Some(server.uri()),
+                ..Default::default()
+            },
+            String::from("token"),
+        )
+    }
+
+    /// Serves `pages` of notifications, optionally with the total count in the headers.
+    async fn mount_pages(server: &MockServer, pages: &[Vec<u64>], total_count: Option<usize>) {
+        for (page, ids) in pages.iter().enumerate() {
+            let mut response = ResponseTemplate::new(200)
+                .set_body_json(ids.iter().map(|id| thread(*id)).collect::<Vec<_>>());
+            if let Some(total_count) = total_count {
+                response =
+                    response.insert_header("x-total-count", total_count.to_string().as_str());
+            }
+
+            Mock::given(method("GET"))
+                .and(path("/notifications"))
+                .and(query_param("page", (page + 1).to_string()))
+                .respond_with(response)
+                .expect(1)
+                .mount(server)
+                .await;
+        }
+    }
+
+    fn ids(notifications: &[ForgeNotification]) -> Vec<&str> {
+        notifications
+            .iter()
+            .map(|notification| notification.id.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reads_pages_smaller_than_asked_for_until_total() {
+        let server = MockServer::start().await;
+        mount_pages(&server, &[vec![1, 2], vec![3, 4], vec![5]], Some(5)).await;
+
+        let notifications = provider(&server).notifications().await.unwrap();
+
+        assert_eq!(ids(&notifications), vec!["1", "2", "3", "4", "5"]);
+    }
+
+    #[tokio::test]
+    async fn stops_when_a_page_repeats() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(vec![thread(1), thread(2)]))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let notifications = provider(&server).notifications().await.unwrap();
+
+        assert_eq!(ids(&notifications), vec!["1", "2"]);
+    }
+
+    #[tokio::test]
+    async fn reads_pages_until_empty_without_total() {
+        let server = MockServer::start().await;
+        mount_pages(&server, &[vec![1, 2], vec![3], vec![]], None).await;
+
+        let notifications = provider(&server).notifications().await.unwrap();
+
+        assert_eq!(ids(&notifications), vec!["1", "2", "3"]);
+    }
+}
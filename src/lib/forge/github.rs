@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use octocrab::models::activity::Notification;
+use octocrab::Octocrab;
+use reqwest::Url;
+
+use super::{BoxedError, ForgeNotification, Provider};
+use crate::config::{Forge, ForgeAccountConfig};
+use crate::github::create_octocrab;
+use crate::github::notifications::{html_url, NotificationPoller};
+
+pub struct GithubProvider {
+    octocrab: Octocrab,
+    poller: NotificationPoller,
+}
+
+impl GithubProvider {
+    pub fn new(config: &ForgeAccountConfig, token: String) -> Option<Self> {
+        Some(Self {
+            octocrab: create_octocrab(config, token)?,
+            poller: NotificationPoller::default(),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Provider for GithubProvider {
+    async fn notifications(&mut self) -> Result<Vec<ForgeNotification>, BoxedError> {
+        self.poller.poll(&self.octocrab).await?;
+
+        Ok(self
+            .poller
+            .notifications
+            .iter()
+            .map(to_forge_notification)
+            .collect())
+    }
+
+    fn next_delay(&self) -> Duration {
+        self.poller.next_delay()
+    }
+
+    async fn html_url(&self, notification: &ForgeNotification) -> Option<Url> {
+        let notification = self
+            .poller
+            .notifications
+            .iter()
+            .find(|x| x.id.to_string() == notification.id)?;
+
+        html_url(&self.octocrab, notification).await
+    }
+
+    async fn mark_read(&self, repo: Option<(&str, &str)>) -> Result<(), BoxedError> {
+        let notifications = self.octocrab.activity().notifications();
+
+        match repo {
+            None => notifications.mark_all_as_read(None).await?,
+            Some((owner, repo)) => notifications.mark_repo_as_read(owner, repo, None).await?,
+        }
+
+        Ok(())
+    }
+}
+
+fn to_forge_notification(notification: &Notification) -> ForgeNotification {
+    ForgeNotification {
+        id: notification.id.to_string(),
+        forge: Forge::Github,
+        repository: notification
+            .repository
+            .full_name
+            .clone()
+            .unwrap_or_else(|| notification.repository.name.clone()),
+        title: notification.subject.title.clone(),
+        kind: notification.subject.r#type.clone(),
+        reason: notification.reason.clone(),
+        updated_at: notification.updated_at,
+        url: None,
+    }
+}
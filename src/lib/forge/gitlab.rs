@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{BoxedError, ForgeNotification, Provider};
+use crate::config::{Forge, ForgeAccountConfig};
+
+/// Pending GitLab To-Dos, see <https://docs.gitlab.com/ee/api/todos.html>
+pub struct GitlabProvider {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Todo {
+    id: u64,
+    project: Option<Project>,
+    action_name: String,
+    target_type: String,
+    target: Option<Target>,
+    target_url: Option<Url>,
+    body: String,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Project {
+    path_with_namespace: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Target {
+    title: Option<String>,
+}
+
+impl GitlabProvider {
+    pub fn new(config: &ForgeAccountConfig, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: config.base_url(),
+            token,
+        }
+    }
+
+    async fn todos(&self) -> Result<Vec<Todo>, BoxedError> {
+        let mut todos = vec![];
+        let mut page = String::from("1");
+
+        // GitLab leaves `x-next-page` empty on the last page
+        while !page.is_empty() {
+            let response = self
+                .client
+                .get(format!("{}/todos", self.base_url))
+                .header("PRIVATE-TOKEN", &self.token)
+                .query(&[("state", "pending"), ("per_page", "100"), ("page", &page)])
+                .send()
+                .await?
+                .error_for_status()?;
+
+            page = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|x| x.to_str().ok())
+                .unwrap_or_default()
+                .to_owned();
+            todos.append(&mut response.json().await?);
+        }
+
+        Ok(todos)
+    }
+
+    async fn mark_done(&self, route: &str) -> Result<(), BoxedError> {
+        self.client
+            .post(format!("{}{route}", self.base_url))
+            .header("PRIVATE-TOKEN", &self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl Provider for GitlabProvider {
+    async fn notifications(&mut self) -> Result<Vec<ForgeNotification>, BoxedError> {
+        Ok(self.todos().await?.into_iter().map(Into::into).collect())
+    }
+
+    async fn mark_read(&self, repo: Option<(&str, &str)>) -> Result<(), BoxedError> {
+        let Some((owner, repo)) = repo else {
+            return self.mark_done("/todos/mark_as_done").await;
+        };
+
+        let path = format!("{owner}/{repo}");
+        for todo in self.todos().await?.into_iter().filter(|todo| {
+            todo.project
+                .as_ref()
+                .is_some_and(|project| project.path_with_namespace == path)
+        }) {
+            self.mark_done(&format!("/todos/{}/mark_as_done", todo.id))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Todo> for ForgeNotification {
+    fn from(todo: Todo) -> Self {
+        let kind = match todo.target_type.as_str() {
+            "MergeRequest" => "PullRequest",
+            target_type => target_type,
+        };
+
+        let reason = match todo.action_name.as_str() {
+            "assigned" => "assign",
+            "mentioned" | "directly_addressed" => "mention",
+            "approval_required" => "review_requested",
+            "build_failed" => "ci_activity",
+            action_name => action_name,
+        };
+
+        ForgeNotification {
+            id: todo.id.to_string(),
+            forge: Forge::Gitlab,
+            repository: todo
+                .project
+                .map(|project| project.path_with_namespace)
+                .unwrap_or_default(),
+            title: todo
+                .target
+                .and_then(|target| target.title)
+                .unwrap_or(todo.body),
+            kind: kind.to_owned(),
+            reason: reason.to_owned(),
+            updated_at: todo.updated_at,
+            url: todo.target_url,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+
+use crate::config::{Forge, ForgeAccountConfig};
+
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod token;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+/// Interval between polls for forges that do not tell us how often to poll.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A notification, to-do or other item that asks for attention on any of the forges.
+#[derive(Clone, Debug)]
+pub struct ForgeNotification {
+    pub id: String,
+    pub forge: Forge,
+    /// Full name of the repository, e.g. `owner/repo`
+    pub repository: String,
+    pub title: String,
+    /// Kind of subject, using the GitHub names such as `PullRequest` and `Issue`
+    pub kind: String,
+    /// Why the notification was sent, using the GitHub names such as `mention`
+    pub reason: String,
+    pub updated_at: DateTime<Utc>,
+    /// Page of the subject, when the forge gives it without further requests
+    pub url: Option<Url>,
+}
+
+#[async_trait(?Send)]
+pub trait Provider {
+    /// Fetches all unread notifications.
+    async fn notifications(&mut self) -> Result<Vec<ForgeNotification>, BoxedError>;
+
+    /// Time to wait before the next call to [`Provider::notifications`].
+    fn next_delay(&self) -> Duration {
+        DEFAULT_POLL_INTERVAL
+    }
+
+    /// Page to open for a notification.
+    async fn html_url(&self, notification: &ForgeNotification) -> Option<Url> {
+        notification.url.clone()
+    }
+
+    /// Marks all notifications as read, or only those of the repository `(owner, repo)`.
+    async fn mark_read(&self, repo: Option<(&str, &str)>) -> Result<(), BoxedError>;
+}
+
+/// Sets up the provider for an account. The error names the account when no token could be
+/// found.
+pub async fn create_provider(config: &ForgeAccountConfig) -> Result<Box<dyn Provider>, BoxedError> {
+    let token = token::resolve(config).await.ok_or_else(|| {
+        format!(
+            "Could not find a token for the {} account {}, {}",
+            forge_name(config.forge),
+            config.name(),
+            token::missing_token_hint(config)
+        )
+    })?;
+
+    Ok(match config.forge {
+        Forge::Github => {
+            Box::new(github::GithubProvider::new(config, token).ok_or_else(|| {
+                format!("Could not set up the GitHub client for {}", config.name())
+            })?)
+        }
+        Forge::Gitlab => Box::new(gitlab::GitlabProvider::new(config, token)),
+        Forge::Gitea => Box::new(gitea::GiteaProvider::new(config, token)),
+    })
+}
+
+pub fn forge_name(forge: Forge) -> &'static str {
+    match forge {
+        Forge::Github => "GitHub",
+        Forge::Gitlab => "GitLab",
+        Forge::Gitea => "Gitea",
+    }
+}
+
+pub fn forge_icon(forge: Forge) -> &'static str {
+    match forge {
+        Forge::Github => "",
+        Forge::Gitlab => "",
+        Forge::Gitea => "",
+    }
+}
+
+/// Remembers which notifications have already been announced, persisted in the XDG state
+/// directory so a restart of the bar does not announce everything again.
+pub struct SeenNotifications {
+    path: PathBuf,
+    seen: HashMap<String, DateTime<Utc>>,
+    initialized: bool,
+}
+
+impl SeenNotifications {
+    /// Loads the seen notifications. A state file that cannot be read starts over as if it did
+    /// not exist, so announcements go on and the file is replaced on the next save.
+    pub fn load(app_name: &str, file_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = xdg::BaseDirectories::with_prefix(app_name)
+            .or(Err("Could not load app state directory"))?
+            .get_state_home()
+            .join(file_name);
+
+        Ok(Self::read(path))
+    }
+
+    fn read(path: PathBuf) -> Self {
+        let seen = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .inspect_err(|e| eprintln!("Could not parse {}: {e}", path.display()))
+                .ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", path.display());
+                None
+            }
+        };
+
+        Self {
+            path,
+            initialized: seen.is_some(),
+            seen: seen.unwrap_or_default(),
+        }
+    }
+
+    /// Returns the notifications that were not seen before, or were updated since, and remembers
+    /// the current set as seen until the next call to [`SeenNotifications::save`]. Nothing is
+    /// reported the very first time, to avoid a flood of notifications.
+    pub fn update<'a>(
+        &mut self,
+        notifications: &'a [ForgeNotification],
+    ) -> Vec<&'a ForgeNotification> {
+        let new_notifications = notifications
+            .iter()
+            .filter(|notification| {
+                self.initialized
+                    && self
+                        .seen
+                        .get(&notification.id)
+                        .is_none_or(|updated_at| notification.updated_at > *updated_at)
+            })
+            .collect();
+
+        self.seen = notifications
+            .iter()
+            .map(|notification| (notification.id.clone(), notification.updated_at))
+            .collect();
+        self.initialized = true;
+
+        new_notifications
+    }
+
+    /// Stores the seen notifications, so they are not announced again after a restart. The file
+    /// is replaced in one step, so a crash while writing cannot leave half of it behind.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let temp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&temp_path, serde_json::to_vec(&self.seen)?)?;
+        std::fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn notification(id: &str, hour: u32) -> ForgeNotification {
+        ForgeNotification {
+            id: id.to_owned(),
+            forge: Forge::Github,
+            repository: String::from("owner/repo"),
+            title: format!("Notification {id}"),
+            kind: String::from("Issue"),
+            reason: String::from("mention"),
+            updated_at: Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap(),
+            url: None,
+        }
+    }
+
+    #[test]
+    fn reports_new_notifications_when_saving_fails() {
+        let mut seen = SeenNotifications {
+            path: PathBuf::from("/dev/null/github-notifications.json"),
+            seen: HashMap::from([(String::from("1"), notification("1", 9).updated_at)]),
+            initialized: true,
+        };
+        let notifications = [
+            notification("1", 9),
+            notification("2", 9),
+            notification("3", 10),
+        ];
+
+        let new_ids = seen
+            .update(&notifications)
+            .into_iter()
+            .map(|notification| notification.id.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(new_ids, vec!["2", "3"]);
+        assert!(seen.save().is_err());
+        assert!(seen.update(&notifications).is_empty());
+    }
+
+    #[test]
+    fn starts_over_after_truncated_state_and_saves_it_again() {
+        let dir =
+            std::env::temp_dir().join(format!("waybar-extensions-seen-{}", std::process::id()));
+        let path = dir.join("state").join("github-notifications.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, br#"{"1": "2024-05-01T09:"#).unwrap();
+
+        let mut seen = SeenNotifications::read(path.clone());
+        let notifications = [notification("1", 9)];
+        // Like a missing file, the first update announces nothing
+        let first_update = seen.update(&notifications).len();
+        let saved = seen.save();
+        let reloaded = SeenNotifications::read(path.clone());
+        let leftovers = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first_update, 0);
+        saved.unwrap();
+        assert!(reloaded.initialized);
+        assert_eq!(reloaded.seen, seen.seen);
+        assert_eq!(leftovers, 1);
+    }
+}
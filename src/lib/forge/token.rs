@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::config::{Forge, ForgeAccountConfig};
+
+/// Finds the token for an account, trying in order the environment, the configured token or
+/// token command and, for GitHub, the `hosts.yml` of the `gh` CLI and finally `gh auth token`,
+/// which also finds tokens `gh` keeps in the system keyring.
+pub async fn resolve(config: &ForgeAccountConfig) -> Option<String> {
+    let host = config.host.as_str();
+
+    if let Some(token) = env_vars(config)
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|token| !token.is_empty())
+    {
+        return Some(token);
+    }
+
+    if let Some(token) = &config.token {
+        return Some(token.clone());
+    }
+
+    if let Some(token_command) = &config.token_command {
+        if let Some(token) = command_output(Command::new("sh").arg("-c").arg(token_command)).await {
+            return Some(token);
+        }
+    }
+
+    if config.forge != Forge::Github {
+        return None;
+    }
+
+    if let Some(token) = gh_hosts_token(host) {
+        return Some(token);
+    }
+
+    command_output(
+        Command::new("gh")
+            .arg("auth")
+            .arg("token")
+            .arg("--hostname")
+            .arg(host),
+    )
+    .await
+}
+
+/// The environment variables that may hold the token of an account. The GitHub ones follow `gh`,
+/// so enterprise hosts use `GH_ENTERPRISE_TOKEN`. `GITLAB_TOKEN` follows `glab` and only applies
+/// to `GITLAB_HOST`, or gitlab.com, so a self-hosted instance never gets the token of another.
+fn env_vars(config: &ForgeAccountConfig) -> &'static [&'static str] {
+    let host = config.host.as_str();
+
+    match config.forge {
+        Forge::Github if host == "github.com" => &["GH_TOKEN", "GITHUB_TOKEN"],
+        Forge::Github => &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"],
+        Forge::Gitlab if host == default_gitlab_host() => &["GITLAB_TOKEN"],
+        Forge::Gitlab => &[],
+        Forge::Gitea => &["GITEA_TOKEN", "FORGEJO_TOKEN"],
+    }
+}
+
+/// Tells where the token of an account can be put, for when [`resolve`] found none.
+pub fn missing_token_hint(config: &ForgeAccountConfig) -> String {
+    let mut sources = env_vars(config).to_vec();
+    sources.extend(["token", "token_command"]);
+    let (last, rest) = sources.split_last().expect("sources are not empty");
+    let hint = format!("set {} or {last}", rest.join(", "));
+
+    match config.forge {
+        Forge::Github => format!("{hint}, or log in with gh"),
+        Forge::Gitlab | Forge::Gitea => hint,
+    }
+}
+
+fn default_gitlab_host() -> String {
+    // `glab` accepts the host with or without a scheme
+    std::env::var("GITLAB_HOST")
+        .ok()
+        .map(|host| {
+            host.trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/')
+                .to_owned()
+        })
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| String::from("gitlab.com"))
+}
+
+async fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let token = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (!token.is_empty()).then_some(token)
+}
+
+/// A host in the `hosts.yml` of the `gh` CLI. Since `gh` 2.40 it keeps the tokens of all
+/// accounts under `users`, with `user` naming the active one. Tokens stored in the system keyring
+/// do not appear at all.
+#[derive(Deserialize)]
+struct GhHost {
+    user: Option<String>,
+    oauth_token: Option<String>,
+    #[serde(default)]
+    users: HashMap<String, Option<GhUser>>,
+}
+
+#[derive(Deserialize)]
+struct GhUser {
+    oauth_token: Option<String>,
+}
+
+/// Reads the token of the active account on a host from the `hosts.yml` file of the `gh` CLI.
+fn gh_hosts_token(host: &str) -> Option<String> {
+    let data = std::fs::read_to_string(gh_config_dir()?.join("hosts.yml")).ok()?;
+    parse_gh_hosts(&data, host)
+}
+
+fn parse_gh_hosts(data: &str, host: &str) -> Option<String> {
+    let mut hosts: HashMap<String, GhHost> = serde_yaml::from_str(data).ok()?;
+    let mut gh_host = hosts.remove(host)?;
+
+    gh_host
+        .user
+        .and_then(|user| gh_host.users.remove(&user)?)
+        .and_then(|user| user.oauth_token)
+        .or(gh_host.oauth_token)
+        .filter(|token| !token.is_empty())
+}
+
+fn gh_config_dir() -> Option<PathBuf> {
+    if let Ok(config_dir) = std::env::var("GH_CONFIG_DIR") {
+        return Some(PathBuf::from(config_dir));
+    }
+
+    Some(
+        xdg::BaseDirectories::with_prefix("gh")
+            .ok()?
+            .get_config_home(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_token_of_active_user() {
+        let hosts = "\
+github.com:
+    users:
+        first:
+            oauth_token: gho_first
+        second:
+            oauth_token: gho_second
+    git_protocol: https
+    user: second
+ghe.example.com:
+    oauth_token: gho_enterprise
+    user: someone
+";
+
+        assert_eq!(
+            parse_gh_hosts(hosts, "github.com").as_deref(),
+            Some("gho_second")
+        );
+        // Older versions only had the token of the one account next to the user
+        assert_eq!(
+            parse_gh_hosts(hosts, "ghe.example.com").as_deref(),
+            Some("gho_enterprise")
+        );
+        assert_eq!(parse_gh_hosts(hosts, "gitlab.com"), None);
+    }
+
+    #[test]
+    fn reads_nothing_from_keyring_accounts() {
+        let hosts = "\
+github.com:
+    users:
+        first:
+    git_protocol: ssh
+    user: first
+";
+
+        assert_eq!(parse_gh_hosts(hosts, "github.com"), None);
+    }
+}
@@ -0,0 +1,28 @@
+use octocrab::Octocrab;
+
+use crate::config::ForgeAccountConfig;
+
+pub mod notifications;
+pub mod pull_requests;
+
+pub fn create_octocrab(config: &ForgeAccountConfig, token: String) -> Option<Octocrab> {
+    build_octocrab(config.base_url(), token)
+}
+
+/// Creates a client for the GraphQL API, which Octocrab requests at `<base>/graphql`. GitHub
+/// Enterprise Server serves it at `/api/graphql` instead of below the REST API at `/api/v3`.
+pub fn create_graphql_octocrab(config: &ForgeAccountConfig, token: String) -> Option<Octocrab> {
+    let base_url = config.base_url();
+    let base_url = base_url.strip_suffix("/v3").unwrap_or(&base_url).to_owned();
+
+    build_octocrab(base_url, token)
+}
+
+fn build_octocrab(base_url: String, token: String) -> Option<Octocrab> {
+    Octocrab::builder()
+        .base_uri(base_url)
+        .ok()?
+        .personal_token(token)
+        .build()
+        .ok()
+}
@@ -0,0 +1,487 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::header::{HeaderMap, HeaderValue, IF_MODIFIED_SINCE, LAST_MODIFIED, RETRY_AFTER};
+use http::StatusCode;
+use octocrab::models::activity::Notification;
+use octocrab::models::repos::Release;
+use octocrab::{FromResponse, Octocrab, Page};
+use reqwest::Url;
+
+use crate::forge::DEFAULT_POLL_INTERVAL;
+
+const NOTIFICATIONS_ROUTE: &str = "/notifications?per_page=50";
+
+/// Upper bound for the back-off after failed or rate-limited requests.
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// Keeps track of the notifications between polls, so unchanged responses do not count against
+/// the rate limit and the poll interval can follow what GitHub asks for.
+pub struct NotificationPoller {
+    pub notifications: Vec<Notification>,
+    last_modified: Option<HeaderValue>,
+    poll_interval: Duration,
+    backoff: Option<Duration>,
+}
+
+impl Default for NotificationPoller {
+    fn default() -> Self {
+        Self {
+            notifications: vec![],
+            last_modified: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            backoff: None,
+        }
+    }
+}
+
+impl NotificationPoller {
+    /// Fetches all pages of notifications, unless GitHub reports nothing changed since the
+    /// previous poll.
+    pub async fn poll(&mut self, octocrab: &Octocrab) -> Result<(), Box<dyn std::error::Error>> {
+        let mut headers = HeaderMap::new();
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+
+        let response = octocrab
+            ._get_with_headers(NOTIFICATIONS_ROUTE, Some(headers))
+            .await
+            .inspect_err(|_| self.increase_backoff(None))?;
+
+        if let Some(poll_interval) = header_secs(response.headers(), "x-poll-interval") {
+            self.poll_interval = poll_interval.clamp(DEFAULT_POLL_INTERVAL, MAX_BACKOFF);
+        }
+
+        match response.status() {
+            StatusCode::NOT_MODIFIED => {
+                self.backoff = None;
+                return Ok(());
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                self.increase_backoff(rate_limit_delay(response.headers()));
+                return Err("Rate limited by GitHub".into());
+            }
+            // Other refusals, like a token without the notifications scope, are errors
+            StatusCode::FORBIDDEN if is_rate_limited(response.headers()) => {
+                self.increase_backoff(rate_limit_delay(response.headers()));
+                return Err("Rate limited by GitHub".into());
+            }
+            _ => {}
+        }
+
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+
+        let response = octocrab::map_github_error(response)
+            .await
+            .inspect_err(|_| self.increase_backoff(None))?;
+        let page = Page::<Notification>::from_response(response).await?;
+        self.notifications = octocrab.all_pages(page).await?;
+
+        self.last_modified = last_modified;
+        self.backoff = None;
+
+        Ok(())
+    }
+
+    /// Time to wait before the next call to [`NotificationPoller::poll`].
+    pub fn next_delay(&self) -> Duration {
+        self.backoff.unwrap_or(self.poll_interval)
+    }
+
+    fn increase_backoff(&mut self, delay: Option<Duration>) {
+        let backoff = match (delay, self.backoff) {
+            (Some(delay), _) => delay,
+            (None, Some(backoff)) => backoff * 2,
+            (None, None) => self.poll_interval,
+        };
+
+        self.backoff = Some(backoff.max(self.poll_interval).min(MAX_BACKOFF));
+    }
+}
+
+/// Tells a 403 for exceeding a rate limit apart from one for missing access.
+fn is_rate_limited(headers: &HeaderMap) -> bool {
+    headers.contains_key(RETRY_AFTER)
+        || header_secs(headers, "x-ratelimit-remaining") == Some(Duration::ZERO)
+}
+
+/// Works out how long GitHub wants us to wait, either through `Retry-After` for secondary rate
+/// limits or the reset time of the primary rate limit.
+fn rate_limit_delay(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(retry_after) = header_secs(headers, RETRY_AFTER.as_str()) {
+        return Some(retry_after);
+    }
+
+    if header_secs(headers, "x-ratelimit-remaining")? != Duration::ZERO {
+        return None;
+    }
+
+    let reset = UNIX_EPOCH + header_secs(headers, "x-ratelimit-reset")?;
+    Some(reset.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Resolves the page on GitHub that belongs to a notification. Only the URL of the API resource
+/// is part of the notification, which is rewritten for pull requests, issues and commits and
+/// fetched for releases. Discussions have no subject URL, so they link to a search in the
+/// repository's discussions instead.
+pub async fn html_url(octocrab: &Octocrab, notification: &Notification) -> Option<Url> {
+    let repository_url = notification.repository.html_url.clone();
+
+    match (
+        notification.subject.r#type.as_str(),
+        &notification.subject.url,
+    ) {
+        ("Release", Some(api_url)) => octocrab
+            .get::<Release, _, _>(api_url.as_str(), None::<&()>)
+            .await
+            .ok()
+            .map(|release| release.html_url),
+        ("Discussion", _) => {
+            let mut url = repository_url?;
+            url.path_segments_mut().ok()?.push("discussions");
+            url.query_pairs_mut()
+                .append_pair("discussions_q", &notification.subject.title);
+            Some(url)
+        }
+        (_, Some(api_url)) => {
+            // `/repos/OWNER/REPO/pulls/1` becomes `/OWNER/REPO/pull/1`, the page of a commit is
+            // `commit`, as `commits` lists the history
+            let (_, path) = api_url.path().split_once("/repos/")?;
+            let mut segments = path.splitn(4, '/');
+            let (owner, repo, kind) = (segments.next()?, segments.next()?, segments.next()?);
+            let kind = match kind {
+                "pulls" => "pull",
+                "commits" => "commit",
+                kind => kind,
+            };
+
+            let mut url = repository_url?;
+            url.set_path(&format!(
+                "{owner}/{repo}/{kind}/{}",
+                segments.next().unwrap_or_default()
+            ));
+            Some(url)
+        }
+        (_, None) => repository_url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use wiremock::matchers::{header_exists, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    const LAST_MODIFIED_TIME: &str = "Wed, 01 May 2024 09:00:00 GMT";
+
+    fn notification(id: u64) -> Value {
+        json!({
+            "id": id.to_string(),
+            "repository": {
+                "id": 1,
+                "name": "repo",
+                "full_name": "owner/repo",
+                "url": "https://api.github.com/repos/owner/repo",
+                "html_url": "https://github.com/owner/repo"
+            },
+            "subject": {
+                "title": format!("Notification {id}"),
+                "url": null,
+                "latest_comment_url": null,
+                "type": "Issue"
+            },
+            "reason": "mention",
+            "unread": true,
+            "updated_at": "2024-05-01T09:00:00Z",
+            "last_read_at": null,
+            "url": format!("https://api.github.com/notifications/threads/{id}")
+        })
+    }
+
+    fn octocrab(server: &MockServer) -> Octocrab {
+        Octocrab::builder()
+            .base_uri(server.uri())
+            .unwrap()
+            .personal_token(String::from("token"))
+            .build()
+            .unwrap()
+    }
+
+    fn ids(poller: &NotificationPoller) -> Vec<u64> {
+        poller
+            .notifications
+            .iter()
+            .map(|notification| *notification.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn follows_link_to_next_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([notification(3)])))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "link",
+                        format!(
+                            "<{}/notifications?per_page=50&page=2>; rel=\"next\"",
+                            server.uri()
+                        )
+                        .as_str(),
+                    )
+                    .set_body_json(json!([notification(1), notification(2)])),
+            )
+            .mount(&server)
+            .await;
+
+        let mut poller = NotificationPoller::default();
+        poller.poll(&octocrab(&server)).await.unwrap();
+
+        assert_eq!(ids(&poller), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn keeps_notifications_when_not_modified() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .and(header_exists("if-modified-since"))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("last-modified", LAST_MODIFIED_TIME)
+                    .set_body_json(json!([notification(1)])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let octocrab = octocrab(&server);
+        let mut poller = NotificationPoller::default();
+        poller.poll(&octocrab).await.unwrap();
+        poller.poll(&octocrab).await.unwrap();
+
+        assert_eq!(ids(&poller), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn follows_poll_interval() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-poll-interval", "600")
+                    .set_body_json(json!([])),
+            )
+            .mount(&server)
+            .await;
+
+        let mut poller = NotificationPoller::default();
+        poller.poll(&octocrab(&server)).await.unwrap();
+
+        assert_eq!(poller.next_delay(), Duration::from_secs(600));
+    }
+
+    #[tokio::test]
+    async fn backs_off_up_to_the_maximum_when_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+
+        let octocrab = octocrab(&server);
+        let mut poller = NotificationPoller::default();
+        let mut delays = vec![];
+        for _ in 0..6 {
+            assert!(poller.poll(&octocrab).await.is_err());
+            delays.push(poller.next_delay().as_secs());
+        }
+
+        assert_eq!(delays, vec![300, 600, 1200, 2400, 3600, 3600]);
+    }
+
+    #[tokio::test]
+    async fn caps_poll_interval_at_the_maximum() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(ResponseTemplate::new(429).insert_header("x-poll-interval", "7200"))
+            .mount(&server)
+            .await;
+
+        let mut poller = NotificationPoller::default();
+        assert!(poller.poll(&octocrab(&server)).await.is_err());
+
+        assert_eq!(poller.next_delay(), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_retry_after_asks() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(ResponseTemplate::new(403).insert_header("retry-after", "900"))
+            .mount(&server)
+            .await;
+
+        let mut poller = NotificationPoller::default();
+        let error = poller.poll(&octocrab(&server)).await.unwrap_err();
+
+        assert_eq!(error.to_string(), "Rate limited by GitHub");
+        assert_eq!(poller.next_delay(), Duration::from_secs(900));
+    }
+
+    fn subject_notification(
+        kind: &str,
+        api_url: Option<String>,
+        repository_url: &str,
+    ) -> Notification {
+        let mut notification = notification(1);
+        notification["subject"]["type"] = json!(kind);
+        notification["subject"]["title"] = json!("Q&A session");
+        notification["subject"]["url"] = json!(api_url);
+        notification["repository"]["html_url"] = json!(repository_url);
+        serde_json::from_value(notification).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_pages_of_subjects() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/releases/7"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "url": format!("{}/repos/owner/repo/releases/7", server.uri()),
+                "html_url": "https://github.com/owner/repo/releases/tag/v1.0",
+                "assets_url": format!("{}/repos/owner/repo/releases/7/assets", server.uri()),
+                "upload_url": "https://uploads.github.com/repos/owner/repo/releases/7/assets",
+                "tarball_url": null,
+                "zipball_url": null,
+                "id": 7,
+                "node_id": "RE_7",
+                "tag_name": "v1.0",
+                "target_commitish": "main",
+                "name": "v1.0",
+                "body": null,
+                "draft": false,
+                "prerelease": false,
+                "created_at": null,
+                "published_at": null,
+                "author": null,
+                "assets": []
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = "https://api.github.com/repos/owner/repo";
+        let enterprise_api = "https://ghe.example.com/api/v3/repos/owner/repo";
+        let cases = [
+            (
+                "PullRequest",
+                Some(format!("{api}/pulls/12")),
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo/pull/12"),
+            ),
+            (
+                "Issue",
+                Some(format!("{api}/issues/34")),
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo/issues/34"),
+            ),
+            (
+                "Commit",
+                Some(format!("{api}/commits/0123abc")),
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo/commit/0123abc"),
+            ),
+            (
+                "PullRequest",
+                Some(format!("{enterprise_api}/pulls/56")),
+                "https://ghe.example.com/owner/repo",
+                Some("https://ghe.example.com/owner/repo/pull/56"),
+            ),
+            (
+                "Discussion",
+                None,
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo/discussions?discussions_q=Q%26A+session"),
+            ),
+            (
+                "Release",
+                Some(format!("{}/repos/owner/repo/releases/7", server.uri())),
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo/releases/tag/v1.0"),
+            ),
+            (
+                "CheckSuite",
+                None,
+                "https://github.com/owner/repo",
+                Some("https://github.com/owner/repo"),
+            ),
+        ];
+
+        let octocrab = octocrab(&server);
+        for (kind, api_url, repository_url, expected) in cases {
+            let notification = subject_notification(kind, api_url, repository_url);
+            assert_eq!(
+                html_url(&octocrab, &notification)
+                    .await
+                    .as_ref()
+                    .map(Url::as_str),
+                expected,
+                "{kind}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_forbidden_without_rate_limit_as_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/notifications"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "4999")
+                    .set_body_json(json!({
+                        "message": "Missing the notifications scope",
+                        "documentation_url": "https://docs.github.com/rest"
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let mut poller = NotificationPoller::default();
+        let error = poller.poll(&octocrab(&server)).await.unwrap_err();
+
+        assert_ne!(error.to_string(), "Rate limited by GitHub");
+    }
+}
@@ -0,0 +1,443 @@
+use chrono::{DateTime, Utc};
+use octocrab::Octocrab;
+use serde::Deserialize;
+use serde_json::json;
+
+const SEARCH_QUERY: &str = r#"
+query($query: String!) {
+  search(query: $query, type: ISSUE, first: 50) {
+    nodes {
+      __typename
+      ... on PullRequest {
+        title
+        createdAt
+        repository { nameWithOwner }
+        commits(last: 1) { nodes { commit { statusCheckRollup { state } } } }
+        timelineItems(itemTypes: [REVIEW_REQUESTED_EVENT], last: 100) {
+          nodes {
+            ... on ReviewRequestedEvent {
+              createdAt
+              requestedReviewer {
+                __typename
+                ... on User { login }
+                ... on Team { combinedSlug }
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+const VIEWER_QUERY: &str = "query { viewer { login } }";
+
+const TEAMS_QUERY: &str = r#"
+query($login: String!) {
+  viewer {
+    organizations(first: 100) {
+      nodes { teams(first: 100, userLogins: [$login]) { nodes { combinedSlug } } }
+    }
+  }
+}
+"#;
+
+/// The user the token belongs to, with the teams that reviews may be requested from
+#[derive(Clone, Default, Debug)]
+pub struct Viewer {
+    pub login: String,
+    /// Teams as `org/team`
+    pub teams: Vec<String>,
+}
+
+pub struct PullRequest {
+    pub title: String,
+    pub repository: String,
+    pub created_at: DateTime<Utc>,
+    /// Combined state of the checks and statuses of the last commit
+    pub ci_state: CiState,
+    /// When a review was last requested from the viewer or one of their teams
+    pub review_requested_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CiState {
+    Success,
+    Pending,
+    Expected,
+    Failure,
+    Error,
+    #[serde(skip)]
+    None,
+}
+
+impl CiState {
+    pub fn is_failing(self) -> bool {
+        matches!(self, CiState::Failure | CiState::Error)
+    }
+}
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct SearchData {
+    search: Nodes<SearchNode>,
+}
+
+#[derive(Deserialize)]
+struct ViewerData<T> {
+    viewer: T,
+}
+
+#[derive(Deserialize)]
+struct LoginNode {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct OrganizationsNode {
+    organizations: Nodes<TeamsNode>,
+}
+
+#[derive(Deserialize)]
+struct TeamsNode {
+    teams: Nodes<TeamNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TeamNode {
+    combined_slug: String,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "__typename")]
+enum SearchNode {
+    PullRequest(PullRequestNode),
+    /// Issues and discussions, which a search without `is:pr` also finds
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequestNode {
+    title: String,
+    created_at: DateTime<Utc>,
+    repository: RepositoryNode,
+    commits: Nodes<CommitNode>,
+    timeline_items: Nodes<ReviewRequestedNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepositoryNode {
+    name_with_owner: String,
+}
+
+#[derive(Deserialize)]
+struct CommitNode {
+    commit: Commit,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Commit {
+    status_check_rollup: Option<StatusCheckRollup>,
+}
+
+#[derive(Deserialize)]
+struct StatusCheckRollup {
+    state: CiState,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewRequestedNode {
+    created_at: DateTime<Utc>,
+    /// Missing when the reviewer was deleted since
+    requested_reviewer: Option<Reviewer>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "__typename")]
+enum Reviewer {
+    User {
+        login: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Team {
+        combined_slug: String,
+    },
+    /// Bots and mannequins
+    #[serde(other)]
+    Other,
+}
+
+impl Reviewer {
+    fn is(&self, viewer: &Viewer) -> bool {
+        match self {
+            Reviewer::User { login } => *login == viewer.login,
+            Reviewer::Team { combined_slug } => viewer.teams.contains(combined_slug),
+            Reviewer::Other => false,
+        }
+    }
+}
+
+/// Finds the user the token belongs to and the teams they are in. Reading the teams needs the
+/// `read:org` scope, without it only review requests to the user are recognized.
+pub async fn viewer(octocrab: &Octocrab) -> Result<Viewer, octocrab::Error> {
+    let response: Response<ViewerData<LoginNode>> =
+        octocrab.graphql(&json!({ "query": VIEWER_QUERY })).await?;
+    let login = response.data.viewer.login;
+
+    let teams = match octocrab
+        .graphql::<Response<ViewerData<OrganizationsNode>>>(&json!({
+            "query": TEAMS_QUERY,
+            "variables": { "login": login },
+        }))
+        .await
+    {
+        Ok(response) => response
+            .data
+            .viewer
+            .organizations
+            .nodes
+            .into_iter()
+            .flat_map(|organization| organization.teams.nodes)
+            .map(|team| team.combined_slug)
+            .collect(),
+        Err(_) => {
+            eprintln!("Could not read your teams, only review requests to you are counted");
+            vec![]
+        }
+    };
+
+    Ok(Viewer { login, teams })
+}
+
+/// Runs a pull request search, e.g. `is:open is:pr review-requested:@me`, through the GraphQL
+/// API, which gives the CI state and review requests without a request per pull request. The
+/// `octocrab` client should come from [`super::create_graphql_octocrab`].
+pub async fn search(
+    octocrab: &Octocrab,
+    query: &str,
+    viewer: &Viewer,
+) -> Result<Vec<PullRequest>, octocrab::Error> {
+    let response: Response<SearchData> = octocrab
+        .graphql(&json!({
+            "query": SEARCH_QUERY,
+            "variables": { "query": query },
+        }))
+        .await?;
+
+    let (pull_requests, others): (Vec<_>, Vec<_>) = response
+        .data
+        .search
+        .nodes
+        .into_iter()
+        .partition(|node| matches!(node, SearchNode::PullRequest(_)));
+    if !others.is_empty() {
+        eprintln!(
+            "Ignoring {} results of \"{query}\" that are not pull requests, add is:pr to the query",
+            others.len()
+        );
+    }
+
+    Ok(pull_requests
+        .into_iter()
+        .filter_map(|node| match node {
+            SearchNode::PullRequest(pull_request) => {
+                Some(PullRequest::from_node(pull_request, viewer))
+            }
+            SearchNode::Other => None,
+        })
+        .collect())
+}
+
+impl PullRequest {
+    fn from_node(node: PullRequestNode, viewer: &Viewer) -> Self {
+        PullRequest {
+            title: node.title,
+            repository: node.repository.name_with_owner,
+            created_at: node.created_at,
+            ci_state: node
+                .commits
+                .nodes
+                .into_iter()
+                .last()
+                .and_then(|commit| commit.commit.status_check_rollup)
+                .map_or(CiState::None, |rollup| rollup.state),
+            review_requested_at: node
+                .timeline_items
+                .nodes
+                .into_iter()
+                .rev()
+                .find(|event| {
+                    event
+                        .requested_reviewer
+                        .as_ref()
+                        .is_some_and(|reviewer| reviewer.is(viewer))
+                })
+                .map(|event| event.created_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::{json, Value};
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::config::ForgeAccountConfig;
+    use crate::github::create_graphql_octocrab;
+
+    /// A client for a GitHub Enterprise Server, whose REST API lives at `/api/v3`.
+    fn enterprise_octocrab(server: &MockServer) -> Octocrab {
+        create_graphql_octocrab(
+            &ForgeAccountConfig {
+                base_url: Some(format!("{}/api/v3", server.uri())),
+                ..Default::default()
+            },
+            String::from("token"),
+        )
+        .unwrap()
+    }
+
+    async fn mount_graphql(server: &MockServer, query_part: &str, data: Value) {
+        Mock::given(method("POST"))
+            .and(path("/api/graphql"))
+            .and(body_string_contains(query_part))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "data": data })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    fn review_requested(created_at: &str, reviewer: Value) -> Value {
+        json!({ "createdAt": created_at, "requestedReviewer": reviewer })
+    }
+
+    fn pull_request(review_requests: Vec<Value>) -> Value {
+        json!({
+            "__typename": "PullRequest",
+            "title": "Fix the build",
+            "createdAt": "2024-05-01T09:00:00Z",
+            "repository": { "nameWithOwner": "owner/repo" },
+            "commits": { "nodes": [
+                { "commit": { "statusCheckRollup": { "state": "FAILURE" } } }
+            ] },
+            "timelineItems": { "nodes": review_requests }
+        })
+    }
+
+    fn me() -> Viewer {
+        Viewer {
+            login: String::from("me"),
+            teams: vec![String::from("owner/reviewers")],
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_viewer_and_teams() {
+        let server = MockServer::start().await;
+        mount_graphql(
+            &server,
+            "viewer { login }",
+            json!({ "viewer": { "login": "me" } }),
+        )
+        .await;
+        mount_graphql(
+            &server,
+            "userLogins",
+            json!({ "viewer": { "organizations": { "nodes": [
+                { "teams": { "nodes": [{ "combinedSlug": "owner/reviewers" }] } },
+                { "teams": { "nodes": [] } }
+            ] } } }),
+        )
+        .await;
+
+        let viewer = viewer(&enterprise_octocrab(&server)).await.unwrap();
+
+        assert_eq!(viewer.login, "me");
+        assert_eq!(viewer.teams, vec!["owner/reviewers"]);
+    }
+
+    #[tokio::test]
+    async fn counts_review_requests_to_viewer_and_teams_only() {
+        let server = MockServer::start().await;
+        mount_graphql(
+            &server,
+            "search(",
+            json!({ "search": { "nodes": [
+                pull_request(vec![
+                    review_requested(
+                        "2024-05-01T10:00:00Z",
+                        json!({ "__typename": "User", "login": "me" }),
+                    ),
+                    review_requested(
+                        "2024-05-01T11:00:00Z",
+                        json!({ "__typename": "Team", "combinedSlug": "owner/reviewers" }),
+                    ),
+                    review_requested(
+                        "2024-05-01T12:00:00Z",
+                        json!({ "__typename": "User", "login": "someone-else" }),
+                    ),
+                    review_requested(
+                        "2024-05-01T13:00:00Z",
+                        json!({ "__typename": "Team", "combinedSlug": "owner/other-team" }),
+                    ),
+                    review_requested("2024-05-01T14:00:00Z", Value::Null),
+                ]),
+                pull_request(vec![review_requested(
+                    "2024-05-01T10:00:00Z",
+                    json!({ "__typename": "Bot", "login": "dependabot" }),
+                )]),
+                { "__typename": "Issue" }
+            ] } }),
+        )
+        .await;
+
+        let pull_requests = search(&enterprise_octocrab(&server), "is:open", &me())
+            .await
+            .unwrap();
+
+        assert_eq!(pull_requests.len(), 2);
+        assert_eq!(
+            pull_requests[0].review_requested_at,
+            Some(Utc.with_ymd_and_hms(2024, 5, 1, 11, 0, 0).unwrap())
+        );
+        assert_eq!(pull_requests[0].ci_state, CiState::Failure);
+        assert_eq!(pull_requests[1].review_requested_at, None);
+    }
+
+    #[tokio::test]
+    async fn fails_on_malformed_pull_requests() {
+        let server = MockServer::start().await;
+        mount_graphql(
+            &server,
+            "search(",
+            json!({ "search": { "nodes": [
+                { "__typename": "PullRequest", "title": "Missing everything else" }
+            ] } }),
+        )
+        .await;
+
+        let result = search(&enterprise_octocrab(&server), "is:pr", &me()).await;
+
+        assert!(result.is_err());
+    }
+}
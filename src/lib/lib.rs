@@ -1,3 +1,12 @@
+pub mod clock;
 pub mod config;
+pub mod emacs;
+pub mod forge;
+pub mod github;
+pub mod logind;
+pub mod mako;
+pub mod notifications;
+pub mod org;
+pub mod systemd;
 pub mod waybar;
 pub mod weather;
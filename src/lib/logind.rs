@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Proxy};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const USER_INTERFACE: &str = "org.freedesktop.login1.User";
+
+/// The login session of the user as logind sees it. Logind only knows the session is idle when
+/// an idle daemon reports it, e.g. `swayidle idlehint 300`.
+pub struct Session {
+    proxy: Proxy<'static>,
+}
+
+impl Session {
+    /// Connects to the session of this process, or to the graphical session of the user when
+    /// this runs outside of one, e.g. as a systemd user service.
+    pub async fn connect() -> zbus::Result<Self> {
+        let connection = Connection::system().await?;
+
+        let session = session_proxy(&connection, "/org/freedesktop/login1/session/auto").await?;
+        if session.get_property::<String>("Id").await.is_ok() {
+            return Ok(Self { proxy: session });
+        }
+
+        let user = Proxy::new(
+            &connection,
+            LOGIND_NAME,
+            "/org/freedesktop/login1/user/self",
+            USER_INTERFACE,
+        )
+        .await?;
+        let (_, path) = user
+            .get_property::<(String, OwnedObjectPath)>("Display")
+            .await?;
+
+        Ok(Self {
+            proxy: session_proxy(&connection, path.as_str()).await?,
+        })
+    }
+
+    /// The moment the session went idle, or `None` while it is in use.
+    pub async fn idle_since(&self) -> zbus::Result<Option<DateTime<Utc>>> {
+        if !self.proxy.get_property::<bool>("IdleHint").await? {
+            return Ok(None);
+        }
+
+        let micros: u64 = self.proxy.get_property("IdleSinceHint").await?;
+        Ok(DateTime::<Utc>::from_timestamp_micros(micros as i64))
+    }
+}
+
+async fn session_proxy(connection: &Connection, path: &str) -> zbus::Result<Proxy<'static>> {
+    Proxy::new(connection, LOGIND_NAME, path.to_owned(), SESSION_INTERFACE).await
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::process::Command;
+
+use serde::Deserialize;
+use zbus::blocking::Connection;
+use zbus::zvariant::OwnedValue;
+
+use crate::notifications::{Notification, NotificationDaemon, Urgency, NOTIFICATIONS_NAME};
+
+type BoxedError = Box<dyn std::error::Error>;
+
+const MAKO_PATH: &str = "/fr/emersion/Mako";
+pub(crate) const MAKO_INTERFACE: &str = "fr.emersion.Mako";
+
+/// A value in the output of `makoctl`. Before mako 1.9 this was the output of `busctl --json`,
+/// which wraps every value as `{"type": "s", "data": ...}`, newer versions give plain values.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum MakoNotificationField<T> {
+    Wrapped { data: T },
+    Plain(T),
+}
+
+impl<T> MakoNotificationField<T> {
+    fn into_inner(self) -> T {
+        match self {
+            MakoNotificationField::Wrapped { data } => data,
+            MakoNotificationField::Plain(data) => data,
+        }
+    }
+}
+
+/// A notification as printed by `makoctl`. Every field is optional and unknown fields are
+/// ignored, so changes in what mako reports do not break the module.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "kebab-case")]
+struct MakoctlNotification {
+    app_name: Option<MakoNotificationField<String>>,
+    app_icon: Option<MakoNotificationField<String>>,
+    category: Option<MakoNotificationField<String>>,
+    summary: Option<MakoNotificationField<String>>,
+    body: Option<MakoNotificationField<String>>,
+    id: Option<MakoNotificationField<u32>>,
+    urgency: Option<MakoNotificationField<u8>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum MakoctlResponse {
+    /// Up to mako 1.8, a list of return values of which the first holds the notifications
+    Wrapped { data: Vec<Vec<MakoctlNotification>> },
+    /// From mako 1.9 on, just the notifications
+    Flat(Vec<MakoctlNotification>),
+}
+
+impl From<MakoctlResponse> for Vec<MakoctlNotification> {
+    fn from(response: MakoctlResponse) -> Self {
+        match response {
+            MakoctlResponse::Wrapped { data } => data.into_iter().next().unwrap_or_default(),
+            MakoctlResponse::Flat(notifications) => notifications,
+        }
+    }
+}
+
+impl From<MakoctlNotification> for Notification {
+    fn from(notification: MakoctlNotification) -> Self {
+        fn string(field: Option<MakoNotificationField<String>>) -> String {
+            field
+                .map(MakoNotificationField::into_inner)
+                .unwrap_or_default()
+        }
+
+        Self {
+            id: notification
+                .id
+                .map(MakoNotificationField::into_inner)
+                .unwrap_or_default(),
+            app_name: string(notification.app_name),
+            app_icon: string(notification.app_icon),
+            category: string(notification.category),
+            summary: string(notification.summary),
+            body: string(notification.body),
+            urgency: notification
+                .urgency
+                .map_or(Urgency::Normal, |urgency| urgency.into_inner().into()),
+        }
+    }
+}
+
+/// Lists the visible notifications through `makoctl list`.
+pub fn list_makoctl() -> Result<Vec<Notification>, BoxedError> {
+    query_makoctl("list")
+}
+
+/// Lists the dismissed notifications that can still be restored through `makoctl history`.
+pub fn history_makoctl() -> Result<Vec<Notification>, BoxedError> {
+    query_makoctl("history")
+}
+
+fn query_makoctl(subcommand: &str) -> Result<Vec<Notification>, BoxedError> {
+    let command_output = Command::new("makoctl").arg(subcommand).output()?;
+    parse_makoctl(&command_output.stdout)
+}
+
+fn parse_makoctl(output: &[u8]) -> Result<Vec<Notification>, BoxedError> {
+    let response: MakoctlResponse = serde_json::from_slice(output)?;
+    let notifications: Vec<MakoctlNotification> = response.into();
+
+    Ok(notifications.into_iter().map(Into::into).collect())
+}
+
+/// Lists the active modes through `makoctl mode`.
+pub fn modes() -> Result<Vec<String>, BoxedError> {
+    let command_output = Command::new("makoctl").arg("mode").output()?;
+
+    Ok(String::from_utf8(command_output.stdout)?
+        .lines()
+        .map(str::trim)
+        .filter(|mode| !mode.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Switches a mode on or off through `makoctl mode -t`.
+pub fn toggle_mode(mode: &str) -> Result<(), BoxedError> {
+    run_makoctl(&["mode", "-t", mode])
+}
+
+/// Brings back the most recently dismissed notification.
+pub fn restore() -> Result<(), BoxedError> {
+    run_makoctl(&["restore"])
+}
+
+pub fn dismiss_all() -> Result<(), BoxedError> {
+    run_makoctl(&["dismiss", "--all"])
+}
+
+/// Invokes the default action of a notification.
+pub fn invoke(id: u32) -> Result<(), BoxedError> {
+    run_makoctl(&["invoke", "-n", &id.to_string()])
+}
+
+fn run_makoctl(args: &[&str]) -> Result<(), BoxedError> {
+    let status = Command::new("makoctl").args(args).status()?;
+
+    if !status.success() {
+        return Err(format!("makoctl {} failed", args.join(" ")).into());
+    }
+
+    Ok(())
+}
+
+/// Talks to mako over the session bus, which is what `makoctl` does under the hood.
+pub struct MakoBus {
+    connection: Connection,
+}
+
+impl MakoBus {
+    pub fn connect() -> zbus::Result<Self> {
+        Ok(Self {
+            connection: Connection::session()?,
+        })
+    }
+
+    pub fn list(&self) -> zbus::Result<Vec<Notification>> {
+        self.query("ListNotifications")
+    }
+
+    pub fn history(&self) -> zbus::Result<Vec<Notification>> {
+        self.query("ListHistory")
+    }
+
+    fn query(&self, method_name: &str) -> zbus::Result<Vec<Notification>> {
+        let notifications: Vec<HashMap<String, OwnedValue>> = self
+            .connection
+            .call_method(
+                Some(NOTIFICATIONS_NAME),
+                MAKO_PATH,
+                Some(MAKO_INTERFACE),
+                method_name,
+                &(),
+            )?
+            .body()
+            .deserialize()?;
+
+        Ok(notifications
+            .iter()
+            .map(|notification| Notification {
+                id: notification
+                    .get("id")
+                    .and_then(|id| u32::try_from(id).ok())
+                    .unwrap_or_default(),
+                app_name: dict_string(notification, "app-name"),
+                app_icon: dict_string(notification, "app-icon"),
+                category: dict_string(notification, "category"),
+                summary: dict_string(notification, "summary"),
+                body: dict_string(notification, "body"),
+                urgency: notification
+                    .get("urgency")
+                    .and_then(|urgency| u8::try_from(urgency).ok())
+                    .map_or(Urgency::Normal, Into::into),
+            })
+            .collect())
+    }
+}
+
+fn dict_string(dict: &HashMap<String, OwnedValue>, key: &str) -> String {
+    dict.get(key)
+        .and_then(|value| <&str>::try_from(value).ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Mako as one of the supported notification daemons, where pausing means switching to the
+/// do-not-disturb mode.
+pub struct Mako {
+    pub dnd_mode: String,
+}
+
+impl NotificationDaemon for Mako {
+    /// Asks mako over the session bus, falling back to `makoctl` when that fails.
+    fn notifications(&self) -> Result<Vec<Notification>, BoxedError> {
+        MakoBus::connect()
+            .and_then(|bus| bus.list())
+            .or_else(|_| list_makoctl())
+    }
+
+    fn history(&self) -> Result<Vec<Notification>, BoxedError> {
+        MakoBus::connect()
+            .and_then(|bus| bus.history())
+            .or_else(|_| history_makoctl())
+    }
+
+    fn paused(&self) -> Result<bool, BoxedError> {
+        Ok(modes()?.contains(&self.dnd_mode))
+    }
+
+    fn modes(&self) -> Result<Vec<String>, BoxedError> {
+        Ok(modes()?
+            .into_iter()
+            .filter(|mode| mode != "default")
+            .collect())
+    }
+
+    fn toggle_pause(&self) -> Result<(), BoxedError> {
+        toggle_mode(&self.dnd_mode)
+    }
+
+    fn restore(&self) -> Result<(), BoxedError> {
+        restore()
+    }
+
+    fn dismiss_all(&self) -> Result<(), BoxedError> {
+        dismiss_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(id: u32, app_name: &str, summary: &str) -> Notification {
+        Notification {
+            id,
+            app_name: app_name.to_owned(),
+            app_icon: String::new(),
+            category: String::new(),
+            summary: summary.to_owned(),
+            body: String::new(),
+            urgency: Urgency::Normal,
+        }
+    }
+
+    fn listed() -> Vec<Notification> {
+        vec![
+            Notification {
+                app_icon: String::from("firefox"),
+                category: String::from("im.received"),
+                body: String::from("Are you coming?"),
+                urgency: Urgency::Critical,
+                ..notification(12, "Firefox", "New message")
+            },
+            // Fields that are left out fall back to their defaults
+            notification(13, "notify-send", "Build finished"),
+        ]
+    }
+
+    fn dismissed() -> Vec<Notification> {
+        vec![Notification {
+            body: String::from("Battery at 10%"),
+            urgency: Urgency::Low,
+            ..notification(7, "upower", "Low battery")
+        }]
+    }
+
+    #[test]
+    fn parses_wrapped_list() {
+        let output = include_bytes!("../../tests/fixtures/makoctl/list-wrapped.json");
+        assert_eq!(parse_makoctl(output).unwrap(), listed());
+    }
+
+    #[test]
+    fn parses_flat_list() {
+        let output = include_bytes!("../../tests/fixtures/makoctl/list-flat.json");
+        assert_eq!(parse_makoctl(output).unwrap(), listed());
+    }
+
+    #[test]
+    fn parses_wrapped_history() {
+        let output = include_bytes!("../../tests/fixtures/makoctl/history-wrapped.json");
+        assert_eq!(parse_makoctl(output).unwrap(), dismissed());
+    }
+
+    #[test]
+    fn parses_flat_history() {
+        let output = include_bytes!("../../tests/fixtures/makoctl/history-flat.json");
+        assert_eq!(parse_makoctl(output).unwrap(), dismissed());
+    }
+
+    #[test]
+    fn parses_data_without_type() {
+        let output = br#"{"data": [[{"id": {"data": 13}, "app-name": {"data": "notify-send"}, "summary": {"data": "Build finished"}}]]}"#;
+        assert_eq!(
+            parse_makoctl(output).unwrap(),
+            vec![notification(13, "notify-send", "Build finished")]
+        );
+    }
+
+    #[test]
+    fn parses_empty_output_of_either_version() {
+        assert_eq!(
+            parse_makoctl(br#"{"type": "aa{sv}", "data": [[]]}"#).unwrap(),
+            vec![]
+        );
+        assert_eq!(parse_makoctl(b"[]").unwrap(), vec![]);
+    }
+}
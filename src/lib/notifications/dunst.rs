@@ -0,0 +1,172 @@
+use std::process::Command;
+
+use serde::Deserialize;
+
+use super::{BoxedError, Notification, NotificationDaemon, Urgency};
+
+/// Dunst, controlled through `dunstctl`. It only exposes the number of shown notifications, not
+/// their contents, so only the history is listed and the module cannot tell whether a critical
+/// notification is shown.
+pub struct Dunst;
+
+#[derive(Deserialize, Debug)]
+struct DunstField<T> {
+    data: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct DunstNotification {
+    id: DunstField<i32>,
+    appname: DunstField<String>,
+    summary: DunstField<String>,
+    body: DunstField<String>,
+    #[serde(default)]
+    icon_path: Option<DunstField<String>>,
+    #[serde(default)]
+    category: Option<DunstField<String>>,
+    #[serde(default)]
+    urgency: Option<DunstField<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DunstctlResponse {
+    data: Vec<Vec<DunstNotification>>,
+}
+
+impl From<DunstNotification> for Notification {
+    fn from(notification: DunstNotification) -> Self {
+        Self {
+            id: notification.id.data as u32,
+            app_name: notification.appname.data,
+            app_icon: notification
+                .icon_path
+                .map(|field| field.data)
+                .unwrap_or_default(),
+            category: notification
+                .category
+                .map(|field| field.data)
+                .unwrap_or_default(),
+            summary: notification.summary.data,
+            body: notification.body.data,
+            urgency: match notification
+                .urgency
+                .as_ref()
+                .map(|field| field.data.as_str())
+            {
+                Some("LOW") => Urgency::Low,
+                Some("CRITICAL") => Urgency::Critical,
+                _ => Urgency::Normal,
+            },
+        }
+    }
+}
+
+/// Interface through which `dunstctl` controls dunst
+pub(crate) const DUNST_INTERFACE: &str = "org.dunstproject.cmd0";
+
+/// Reads the output of `dunstctl history`, which wraps every value like `busctl --json` does.
+fn parse_history(output: &[u8]) -> Result<Vec<Notification>, BoxedError> {
+    let response: DunstctlResponse = serde_json::from_slice(output)?;
+
+    Ok(response
+        .data
+        .into_iter()
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
+
+fn dunstctl(args: &[&str]) -> Result<String, BoxedError> {
+    let command_output = Command::new("dunstctl").args(args).output()?;
+
+    if !command_output.status.success() {
+        return Err(format!("dunstctl {} failed", args.join(" ")).into());
+    }
+
+    Ok(String::from_utf8(command_output.stdout)?.trim().to_owned())
+}
+
+impl NotificationDaemon for Dunst {
+    /// Neither `dunstctl` nor the D-Bus interface of dunst list the shown notifications.
+    fn notifications(&self) -> Result<Vec<Notification>, BoxedError> {
+        Ok(vec![])
+    }
+
+    /// Counts the shown notifications together with those held back while paused.
+    fn count(&self) -> Result<usize, BoxedError> {
+        let displayed: usize = dunstctl(&["count", "displayed"])?.parse()?;
+        let waiting: usize = dunstctl(&["count", "waiting"])?.parse()?;
+
+        Ok(displayed + waiting)
+    }
+
+    fn history(&self) -> Result<Vec<Notification>, BoxedError> {
+        parse_history(dunstctl(&["history"])?.as_bytes())
+    }
+
+    fn paused(&self) -> Result<bool, BoxedError> {
+        Ok(dunstctl(&["is-paused"])? == "true")
+    }
+
+    fn toggle_pause(&self) -> Result<(), BoxedError> {
+        dunstctl(&["set-paused", "toggle"]).map(drop)
+    }
+
+    fn restore(&self) -> Result<(), BoxedError> {
+        dunstctl(&["history-pop"]).map(drop)
+    }
+
+    fn dismiss_all(&self) -> Result<(), BoxedError> {
+        dunstctl(&["close-all"]).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(id: u32, app_name: &str, summary: &str, urgency: Urgency) -> Notification {
+        Notification {
+            id,
+            app_name: app_name.to_owned(),
+            app_icon: String::new(),
+            category: String::new(),
+            summary: summary.to_owned(),
+            body: String::new(),
+            urgency,
+        }
+    }
+
+    #[test]
+    fn parses_history() {
+        let output = include_bytes!("../../../tests/fixtures/dunstctl/history.json");
+
+        assert_eq!(
+            parse_history(output).unwrap(),
+            [
+                Notification {
+                    app_icon: String::from("/usr/share/icons/Adwaita/battery-low.svg"),
+                    category: String::from("device"),
+                    body: String::from("Battery at 10%"),
+                    ..notification(7, "upower", "Low battery", Urgency::Critical)
+                },
+                notification(5, "notify-send", "Build finished", Urgency::Low),
+                // Fields that are left out fall back to their defaults
+                Notification {
+                    body: String::from("Are you coming?"),
+                    ..notification(3, "Firefox", "New message", Urgency::Normal)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_empty_history() {
+        assert_eq!(
+            parse_history(br#"{"type": "aa{sv}", "data": [[]]}"#).unwrap(),
+            []
+        );
+    }
+}
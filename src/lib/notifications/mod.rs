@@ -0,0 +1,633 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use zbus::blocking::fdo::MonitoringProxy;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::MatchRule;
+
+use crate::config::{MakoConfig, NotificationDaemonKind, NotificationsConfig};
+use crate::mako::{Mako, MAKO_INTERFACE};
+use crate::waybar::{escape_markup, truncate};
+
+pub mod dunst;
+pub mod swaync;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+pub const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// Time to let the daemon handle a call before asking for the new state
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub id: u32,
+    pub app_name: String,
+    pub app_icon: String,
+    pub category: String,
+    pub summary: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl From<u8> for Urgency {
+    fn from(urgency: u8) -> Self {
+        match urgency {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+}
+
+/// The parts of a notification daemon shown in and controlled from the bar. Not every daemon
+/// can list its notifications, so those only report a count.
+pub trait NotificationDaemon {
+    /// Notifications that are shown, or are waiting to be shown while paused. Empty for daemons
+    /// that cannot list them, which therefore never get the `critical` class.
+    fn notifications(&self) -> Result<Vec<Notification>, BoxedError>;
+
+    fn count(&self) -> Result<usize, BoxedError> {
+        Ok(self.notifications()?.len())
+    }
+
+    /// Dismissed notifications that can still be restored.
+    fn history(&self) -> Result<Vec<Notification>, BoxedError> {
+        Ok(vec![])
+    }
+
+    /// Whether notifications are held back, e.g. by a do-not-disturb mode.
+    fn paused(&self) -> Result<bool, BoxedError>;
+
+    /// Active modes other than the default one, for daemons that have them.
+    fn modes(&self) -> Result<Vec<String>, BoxedError> {
+        Ok(vec![])
+    }
+
+    fn toggle_pause(&self) -> Result<(), BoxedError>;
+
+    /// Brings back the most recently dismissed notification.
+    fn restore(&self) -> Result<(), BoxedError>;
+
+    fn dismiss_all(&self) -> Result<(), BoxedError>;
+}
+
+#[derive(Serialize)]
+pub struct WaybarResponse {
+    pub text: String,
+    pub tooltip: String,
+    pub alt: String,
+    pub class: Vec<String>,
+}
+
+/// Shows the number of notifications, with the active modes and whether notifications are paused
+/// or critical as classes.
+pub fn waybar_response(
+    config: &MakoConfig,
+    daemon: &dyn NotificationDaemon,
+) -> Result<WaybarResponse, BoxedError> {
+    let notifications = daemon.notifications()?;
+    let history = daemon.history().unwrap_or_default();
+    let paused = daemon.paused().unwrap_or_default();
+    let modes = daemon.modes().unwrap_or_default();
+
+    let alt = if paused {
+        String::from("do-not-disturb")
+    } else {
+        String::from("default")
+    };
+
+    // Only mako lists its notifications, the other daemons never show as critical
+    let critical = notifications
+        .iter()
+        .any(|notification| notification.urgency == Urgency::Critical);
+
+    let class = modes
+        .into_iter()
+        .chain(paused.then(|| String::from("dnd")))
+        .chain(critical.then(|| String::from("critical")))
+        .collect();
+
+    Ok(WaybarResponse {
+        text: daemon.count()?.to_string(),
+        tooltip: tooltip(
+            &notifications,
+            &history,
+            config.history_size,
+            config.body_length,
+        ),
+        alt,
+        class,
+    })
+}
+
+/// Picks the configured daemon, or asks the one running on the session bus who it is.
+pub fn detect(config: &NotificationsConfig) -> Result<Box<dyn NotificationDaemon>, BoxedError> {
+    let kind = match config.daemon {
+        NotificationDaemonKind::Auto => server_name()?.parse()?,
+        kind => kind,
+    };
+
+    Ok(match kind {
+        NotificationDaemonKind::Mako | NotificationDaemonKind::Auto => Box::new(Mako {
+            dnd_mode: config.mako.dnd_mode.clone(),
+        }),
+        NotificationDaemonKind::Dunst => Box::new(dunst::Dunst),
+        NotificationDaemonKind::Swaync => Box::new(swaync::Swaync),
+    })
+}
+
+fn server_name() -> zbus::Result<String> {
+    let (name, _vendor, _version, _spec_version): (String, String, String, String) =
+        Connection::session()?
+            .call_method(
+                Some(NOTIFICATIONS_NAME),
+                NOTIFICATIONS_PATH,
+                Some(NOTIFICATIONS_NAME),
+                "GetServerInformation",
+                &(),
+            )?
+            .body()
+            .deserialize()?;
+
+    Ok(name)
+}
+
+/// Calls `on_change` every time the set of notifications may have changed. Nothing announces new
+/// notifications on the bus, so this monitors the calls made to the notification daemon, the
+/// signals it sends when notifications are closed and those of the swaync control center. Only
+/// returns on errors.
+pub fn watch_bus(on_change: impl FnMut()) -> zbus::Result<()> {
+    watch_connection(Connection::session()?, on_change)
+}
+
+fn watch_connection(monitor: Connection, mut on_change: impl FnMut()) -> zbus::Result<()> {
+    // Match rules only take the unique name of a connection as destination, which changes when
+    // the daemon restarts, so calls are matched by the interfaces of the daemons instead
+    let call_interfaces = [
+        NOTIFICATIONS_NAME,
+        MAKO_INTERFACE,
+        dunst::DUNST_INTERFACE,
+        swaync::SWAYNC_INTERFACE,
+    ];
+    // Toggling do-not-disturb and the control center of swaync go through its own interface
+    let signal_interfaces = [NOTIFICATIONS_NAME, swaync::SWAYNC_INTERFACE];
+
+    let mut rules = vec![];
+    for interface in call_interfaces {
+        rules.push(
+            MatchRule::builder()
+                .msg_type(Type::MethodCall)
+                .interface(interface)?
+                .build(),
+        );
+    }
+    for interface in signal_interfaces {
+        rules.push(
+            MatchRule::builder()
+                .msg_type(Type::Signal)
+                .interface(interface)?
+                .build(),
+        );
+    }
+    MonitoringProxy::new(&monitor)?.become_monitor(&rules, 0)?;
+
+    for message in MessageIterator::from(monitor) {
+        let message = message?;
+        let header = message.header();
+
+        // Reading the state does not change it, and would otherwise loop on our own calls
+        if header
+            .member()
+            .is_some_and(|member| member.contains("List") || member.starts_with("Get"))
+        {
+            continue;
+        }
+
+        std::thread::sleep(SETTLE_TIME);
+        on_change();
+    }
+
+    Ok(())
+}
+
+/// Lists the notifications grouped per application, followed by the most recent dismissed ones.
+pub fn tooltip(
+    notifications: &[Notification],
+    history: &[Notification],
+    history_size: usize,
+    body_length: usize,
+) -> String {
+    let mut tooltip = group_by_app(notifications)
+        .iter()
+        .map(|(app_name, notifications)| {
+            let entries = notifications
+                .iter()
+                .map(|notification| format_notification(notification, body_length))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!(
+                "<b>{} ({})</b>\n{entries}",
+                escape_markup(app_name),
+                notifications.len()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if !history.is_empty() && history_size > 0 {
+        let recent = history
+            .iter()
+            .take(history_size)
+            .map(|y| escape_markup(&y.summary))
+            .collect::<Vec<_>>()
+            .join("\n");
+        tooltip = format!("{tooltip}\n\n<b>History</b>\n{recent}");
+    }
+
+    tooltip
+}
+
+/// Groups notifications per application, keeping the order in which the applications first
+/// appear.
+fn group_by_app(notifications: &[Notification]) -> Vec<(&str, Vec<&Notification>)> {
+    let mut groups: Vec<(&str, Vec<&Notification>)> = vec![];
+
+    for notification in notifications {
+        match groups
+            .iter_mut()
+            .find(|(app_name, _)| *app_name == notification.app_name)
+        {
+            Some((_, group)) => group.push(notification),
+            None => groups.push((&notification.app_name, vec![notification])),
+        }
+    }
+
+    groups
+}
+
+fn format_notification(notification: &Notification, body_length: usize) -> String {
+    let summary = escape_markup(&notification.summary);
+    let summary = if notification.urgency == Urgency::Critical {
+        format!("<b>{summary}</b>")
+    } else {
+        summary
+    };
+
+    let body = notification
+        .body
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if body.is_empty() || body_length == 0 {
+        return summary;
+    }
+
+    format!(
+        "{summary}\n    <small>{}</small>",
+        escape_markup(&truncate(&body, body_length))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+
+    use zbus::blocking::connection;
+    use zbus::message::Flags;
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    fn notification(app_name: &str, summary: &str, body: &str) -> Notification {
+        Notification {
+            id: 1,
+            app_name: app_name.to_owned(),
+            app_icon: String::new(),
+            category: String::new(),
+            summary: summary.to_owned(),
+            body: body.to_owned(),
+            urgency: Urgency::Normal,
+        }
+    }
+
+    #[test]
+    fn groups_by_app_in_order_of_appearance() {
+        let notifications = [
+            notification("Firefox", "New message", ""),
+            notification("upower", "Low battery", ""),
+            notification("Firefox", "Download finished", ""),
+        ];
+
+        let groups = group_by_app(&notifications)
+            .into_iter()
+            .map(|(app_name, group)| {
+                let summaries = group.iter().map(|n| n.summary.as_str()).collect::<Vec<_>>();
+                (app_name, summaries)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            groups,
+            [
+                ("Firefox", vec!["New message", "Download finished"]),
+                ("upower", vec!["Low battery"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_notifications_per_app_and_lists_history() {
+        let notifications = [
+            notification("Firefox", "New message", ""),
+            notification("upower", "Low battery", ""),
+            notification("Firefox", "Download finished", ""),
+        ];
+        let history = [
+            notification("notify-send", "Build <failed>", ""),
+            notification("notify-send", "Build finished", ""),
+        ];
+
+        assert_eq!(
+            tooltip(&notifications, &history, 1, 80),
+            [
+                "<b>Firefox (2)</b>",
+                "New message",
+                "Download finished",
+                "",
+                "<b>upower (1)</b>",
+                "Low battery",
+                "",
+                "<b>History</b>",
+                "Build &lt;failed&gt;",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn leaves_out_history_when_its_size_is_zero() {
+        let notifications = [notification("upower", "Low battery", "")];
+        let history = [notification("notify-send", "Build finished", "")];
+
+        assert_eq!(
+            tooltip(&notifications, &history, 0, 80),
+            "<b>upower (1)</b>\nLow battery"
+        );
+    }
+
+    #[test]
+    fn escapes_body_and_collapses_whitespace() {
+        let notification = notification("notify-send", "Q&A", "  Use <b>\n\n  bold</b>\tmarkup  ");
+
+        assert_eq!(
+            format_notification(&notification, 80),
+            "Q&amp;A\n    <small>Use &lt;b&gt; bold&lt;/b&gt; markup</small>"
+        );
+    }
+
+    #[test]
+    fn truncates_body_at_body_length() {
+        let notification = notification("upower", "Low battery", "Battery at 10%, plug in soon");
+
+        assert_eq!(
+            format_notification(&notification, 14),
+            "Low battery\n    <small>Battery at 10…</small>"
+        );
+        assert_eq!(format_notification(&notification, 0), "Low battery");
+    }
+
+    #[test]
+    fn leaves_out_empty_body() {
+        let notification = notification("upower", "Low battery", " \n ");
+
+        assert_eq!(format_notification(&notification, 80), "Low battery");
+    }
+
+    #[test]
+    fn shows_critical_summaries_in_bold() {
+        let critical = Notification {
+            urgency: Urgency::Critical,
+            ..notification("upower", "Battery <5%", "")
+        };
+        let low = Notification {
+            urgency: Urgency::Low,
+            ..notification("upower", "Battery <50%", "")
+        };
+
+        assert_eq!(format_notification(&critical, 80), "<b>Battery &lt;5%</b>");
+        assert_eq!(format_notification(&low, 80), "Battery &lt;50%");
+    }
+
+    /// A daemon that reports a fixed state
+    #[derive(Default)]
+    struct FakeDaemon {
+        notifications: Vec<Notification>,
+        history: Vec<Notification>,
+        paused: bool,
+        modes: Vec<String>,
+    }
+
+    impl NotificationDaemon for FakeDaemon {
+        fn notifications(&self) -> Result<Vec<Notification>, BoxedError> {
+            Ok(self.notifications.clone())
+        }
+
+        fn history(&self) -> Result<Vec<Notification>, BoxedError> {
+            Ok(self.history.clone())
+        }
+
+        fn paused(&self) -> Result<bool, BoxedError> {
+            Ok(self.paused)
+        }
+
+        fn modes(&self) -> Result<Vec<String>, BoxedError> {
+            Ok(self.modes.clone())
+        }
+
+        fn toggle_pause(&self) -> Result<(), BoxedError> {
+            unimplemented!()
+        }
+
+        fn restore(&self) -> Result<(), BoxedError> {
+            unimplemented!()
+        }
+
+        fn dismiss_all(&self) -> Result<(), BoxedError> {
+            unimplemented!()
+        }
+    }
+
+    fn critical(summary: &str) -> Notification {
+        Notification {
+            urgency: Urgency::Critical,
+            ..notification("upower", summary, "")
+        }
+    }
+
+    fn class(daemon: &FakeDaemon) -> Vec<String> {
+        waybar_response(&MakoConfig::default(), daemon)
+            .unwrap()
+            .class
+    }
+
+    #[test]
+    fn marks_critical_notifications() {
+        let daemon = FakeDaemon {
+            notifications: vec![
+                notification("upower", "Battery <50%", ""),
+                critical("Battery <5%"),
+            ],
+            ..FakeDaemon::default()
+        };
+
+        assert_eq!(class(&daemon), ["critical"]);
+    }
+
+    #[test]
+    fn ignores_critical_notifications_in_history() {
+        let daemon = FakeDaemon {
+            history: vec![critical("Battery <5%")],
+            ..FakeDaemon::default()
+        };
+
+        assert!(class(&daemon).is_empty());
+    }
+
+    #[test]
+    fn adds_modes_and_dnd_before_critical() {
+        let daemon = FakeDaemon {
+            notifications: vec![critical("Battery <5%")],
+            paused: true,
+            modes: vec![String::from("do-not-disturb")],
+            ..FakeDaemon::default()
+        };
+        let response = waybar_response(&MakoConfig::default(), &daemon).unwrap();
+
+        assert_eq!(response.class, ["do-not-disturb", "dnd", "critical"]);
+        assert_eq!(response.alt, "do-not-disturb");
+        assert_eq!(response.text, "1");
+    }
+
+    /// A session bus of its own, so the test neither needs nor disturbs the one of the desktop
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--print-address", "--nofork"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon is needed to test watching the bus");
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+
+            Self {
+                daemon,
+                address: address.trim().to_owned(),
+            }
+        }
+
+        fn connect(&self) -> Connection {
+            connection::Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Calls a method of the notification daemon without waiting for an answer.
+    fn call<B>(client: &Connection, method_name: &str, body: &B)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let message = zbus::Message::method(NOTIFICATIONS_PATH, method_name)
+            .unwrap()
+            .destination(NOTIFICATIONS_NAME)
+            .unwrap()
+            .interface(NOTIFICATIONS_NAME)
+            .unwrap()
+            .with_flags(Flags::NoReplyExpected)
+            .unwrap()
+            .build(body)
+            .unwrap();
+        client.send(&message).unwrap();
+    }
+
+    fn notify(client: &Connection) {
+        call(
+            client,
+            "Notify",
+            &(
+                "notify-send",
+                0u32,
+                "",
+                "Build finished",
+                "",
+                Vec::<&str>::new(),
+                HashMap::<&str, Value>::new(),
+                -1i32,
+            ),
+        );
+    }
+
+    #[test]
+    fn calls_on_change_for_new_notifications_only() {
+        let bus = TestBus::start();
+        // Stands in for the notification daemon, which never answers
+        let daemon = bus.connect();
+        daemon.request_name(NOTIFICATIONS_NAME).unwrap();
+        let client = bus.connect();
+
+        let (changes, changed) = mpsc::channel();
+        let monitor = bus.connect();
+        std::thread::spawn(move || watch_connection(monitor, || changes.send(()).unwrap()));
+
+        // Becoming a monitor takes a moment, until then the calls go unseen
+        let timeout = Duration::from_secs(1);
+        let mut attempts = 0;
+        while changed.recv_timeout(timeout).is_err() {
+            attempts += 1;
+            assert!(attempts < 10, "the watcher never saw a notification");
+            notify(&client);
+        }
+        while changed.recv_timeout(timeout).is_ok() {}
+
+        // What the modules themselves ask for must not count as a change
+        call(&client, "GetServerInformation", &());
+        call(&client, "GetCapabilities", &());
+        call(&client, "ListNotifications", &());
+        call(&client, "ListHistory", &());
+        notify(&client);
+
+        assert!(changed.recv_timeout(timeout).is_ok());
+        assert!(changed.recv_timeout(timeout).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use std::process::Command;
+
+use super::{BoxedError, Notification, NotificationDaemon};
+
+/// Interface of the swaync control center, which signals every change to the notifications or
+/// do-not-disturb mode
+pub const SWAYNC_INTERFACE: &str = "org.erikreider.swaync.cc";
+
+/// SwayNotificationCenter, controlled through `swaync-client`. It keeps its notifications in its
+/// own control center and only reports how many there are, so the module cannot tell whether a
+/// critical notification is shown.
+pub struct Swaync;
+
+fn swaync_client(args: &[&str]) -> Result<String, BoxedError> {
+    let command_output = Command::new("swaync-client").args(args).output()?;
+
+    if !command_output.status.success() {
+        return Err(format!("swaync-client {} failed", args.join(" ")).into());
+    }
+
+    Ok(String::from_utf8(command_output.stdout)?.trim().to_owned())
+}
+
+impl NotificationDaemon for Swaync {
+    fn notifications(&self) -> Result<Vec<Notification>, BoxedError> {
+        Ok(vec![])
+    }
+
+    fn count(&self) -> Result<usize, BoxedError> {
+        Ok(swaync_client(&["--count", "--skip-wait"])?.parse()?)
+    }
+
+    fn paused(&self) -> Result<bool, BoxedError> {
+        Ok(swaync_client(&["--get-dnd", "--skip-wait"])? == "true")
+    }
+
+    fn toggle_pause(&self) -> Result<(), BoxedError> {
+        swaync_client(&["--toggle-dnd", "--skip-wait"]).map(drop)
+    }
+
+    fn restore(&self) -> Result<(), BoxedError> {
+        Err("swaync does not keep a history to restore from".into())
+    }
+
+    fn dismiss_all(&self) -> Result<(), BoxedError> {
+        swaync_client(&["--close-all", "--skip-wait"]).map(drop)
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Todo keywords to leave out of headings, the defaults of org and the ones in common use
+const TODO_KEYWORDS: [&str; 10] = [
+    "TODO",
+    "NEXT",
+    "STARTED",
+    "WAIT",
+    "WAITING",
+    "HOLD",
+    "DONE",
+    "CANCELED",
+    "CANCELLED",
+    "PROJ",
+];
+
+/// A `CLOCK:` line in an org file, together with the heading it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockEntry {
+    pub heading: String,
+    pub start: NaiveDateTime,
+    /// `None` while the clock is still running
+    pub end: Option<NaiveDateTime>,
+    /// The `Effort` property of the heading
+    pub effort: Option<Duration>,
+}
+
+/// Collects the org files to read, where directories contribute the `.org` files directly in
+/// them, like `org-agenda-files` does. A leading `~` stands for the home directory.
+pub fn org_files(paths: &[String]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .map(|path| expand_home(path))
+        .flat_map(|path| {
+            if path.is_dir() {
+                path.read_dir()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|x| x == "org"))
+                    .collect()
+            } else {
+                vec![path]
+            }
+        })
+        .collect()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME");
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => PathBuf::from(home),
+        (Some(rest), Some(home)) if rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn read_clock_entries(path: &Path) -> std::io::Result<Vec<ClockEntry>> {
+    Ok(parse_clock_entries(&std::fs::read_to_string(path)?))
+}
+
+/// Reads the clock entries of all org files among the paths. Files that cannot be read are
+/// skipped with a message, only when none of them can be read is that an error.
+pub fn clock_entries(paths: &[String]) -> std::io::Result<Vec<ClockEntry>> {
+    let mut entries = vec![];
+    let mut last_error = None;
+    let mut read_any = false;
+    for path in org_files(paths) {
+        match read_clock_entries(&path) {
+            Ok(mut file_entries) => {
+                entries.append(&mut file_entries);
+                read_any = true;
+            }
+            Err(e) => {
+                eprintln!("Could not read {}: {e}", path.display());
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match last_error {
+        Some(e) if !read_any => Err(e),
+        _ => Ok(entries),
+    }
+}
+
+/// Finds the running clock among the entries. Should more than one be open, the one started last
+/// is the one Emacs would consider running.
+pub fn open_clock(entries: &[ClockEntry]) -> Option<&ClockEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.end.is_none())
+        .max_by_key(|entry| entry.start)
+}
+
+/// Sums the time clocked on each heading between `from` and `to`, longest first. Entries are cut
+/// off at the edges of the range and running clocks count until `to`.
+pub fn clocked_time(
+    entries: &[ClockEntry],
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> Vec<(String, Duration)> {
+    let mut totals: HashMap<&str, Duration> = HashMap::new();
+    for entry in entries {
+        let start = entry.start.max(from);
+        let end = entry.end.unwrap_or(to).min(to);
+        if end > start {
+            *totals.entry(&entry.heading).or_insert_with(Duration::zero) += end - start;
+        }
+    }
+
+    let mut totals = totals
+        .into_iter()
+        .map(|(heading, duration)| (heading.to_owned(), duration))
+        .collect::<Vec<_>>();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    totals
+}
+
+pub fn parse_clock_entries(text: &str) -> Vec<ClockEntry> {
+    let mut heading = None;
+    let mut effort = None;
+    let mut entries = vec![];
+
+    for line in text.lines() {
+        if let Some(title) = parse_heading(line) {
+            heading = Some(title);
+            effort = None;
+        } else if let Some(value) = line.trim().strip_prefix(":Effort:") {
+            effort = parse_duration(value.trim());
+        } else if let (Some(heading), Some(clock)) = (&heading, line.trim().strip_prefix("CLOCK:"))
+        {
+            if let Some((start, end)) = parse_clock(clock) {
+                entries.push(ClockEntry {
+                    heading: heading.clone(),
+                    start,
+                    end,
+                    effort,
+                });
+            }
+        }
+    }
+
+    entries
+}
+
+/// Extracts the title of a heading like `** TODO [#A] Write report :work:`, leaving out the todo
+/// keyword, priority and tags as `org-clock-heading` does.
+fn parse_heading(line: &str) -> Option<String> {
+    let title = line.trim_start_matches('*');
+    if title.len() == line.len() || !title.starts_with(' ') {
+        return None;
+    }
+
+    let mut words = title.split_whitespace().peekable();
+    if words
+        .peek()
+        .is_some_and(|word| TODO_KEYWORDS.contains(word))
+    {
+        words.next();
+    }
+    if words
+        .peek()
+        .is_some_and(|word| word.starts_with("[#") && word.ends_with(']'))
+    {
+        words.next();
+    }
+
+    let mut words = words.collect::<Vec<_>>();
+    if words
+        .last()
+        .is_some_and(|word| word.len() > 1 && word.starts_with(':') && word.ends_with(':'))
+    {
+        words.pop();
+    }
+
+    Some(words.join(" "))
+}
+
+/// Parses `[2024-05-01 Wed 09:12]--[2024-05-01 Wed 10:30] =>  1:18`, where an open clock only has
+/// the first timestamp.
+fn parse_clock(clock: &str) -> Option<(NaiveDateTime, Option<NaiveDateTime>)> {
+    let mut timestamps = clock
+        .split(['[', ']'])
+        .map(str::trim)
+        .filter(|part| part.chars().next().is_some_and(|c| c.is_ascii_digit()));
+
+    let start = parse_timestamp(timestamps.next()?)?;
+    let end = timestamps.next().and_then(parse_timestamp);
+
+    Some((start, end))
+}
+
+/// Parses the inside of an org timestamp, skipping the day name as it may be localized.
+fn parse_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    let mut parts = timestamp.split_whitespace();
+    let date = NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?;
+    let time = parts
+        .find_map(|part| NaiveTime::parse_from_str(part, "%H:%M").ok())
+        .unwrap_or_default();
+
+    Some(date.and_time(time))
+}
+
+/// Parses an org duration like `1:30`, `1:30:00`, `90min` or `1d 2h`, using the default units of
+/// `org-duration-units`. A plain number counts as minutes.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    if text.contains(':') {
+        let parts = text
+            .split(':')
+            .map(|part| part.trim().parse::<i64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        return match parts[..] {
+            [hours, minutes] => Some(Duration::minutes(hours * 60 + minutes)),
+            [hours, minutes, seconds] => {
+                Some(Duration::seconds((hours * 60 + minutes) * 60 + seconds))
+            }
+            _ => None,
+        };
+    }
+
+    let mut minutes = 0f64;
+    for part in text.split_whitespace() {
+        let unit_start = part
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(part.len());
+        let (value, unit) = part.split_at(unit_start);
+        let value: f64 = value.parse().ok()?;
+        let unit_minutes = match unit {
+            "" | "min" => 1.0,
+            "h" => 60.0,
+            "d" => 60.0 * 24.0,
+            "w" => 60.0 * 24.0 * 7.0,
+            "m" => 60.0 * 24.0 * 30.0,
+            "y" => 60.0 * 24.0 * 365.25,
+            _ => return None,
+        };
+        minutes += value * unit_minutes;
+    }
+
+    (minutes > 0.0).then(|| Duration::minutes(minutes.round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn strips_todo_keyword_priority_and_tags_from_headings() {
+        assert_eq!(
+            parse_heading("** TODO [#A] Write report :work:urgent:").as_deref(),
+            Some("Write report")
+        );
+        assert_eq!(
+            parse_heading("* DONE Write report").as_deref(),
+            Some("Write report")
+        );
+        assert_eq!(
+            parse_heading("*** [#B] Review   pull request").as_deref(),
+            Some("Review pull request")
+        );
+        // Only a leading keyword is one, and only a trailing group of tags
+        assert_eq!(
+            parse_heading("* Finish TODO list :: before :lunch").as_deref(),
+            Some("Finish TODO list :: before :lunch")
+        );
+    }
+
+    #[test]
+    fn does_not_take_bold_text_for_a_heading() {
+        assert_eq!(parse_heading("*bold* statement"), None);
+        assert_eq!(parse_heading("**"), None);
+        assert_eq!(parse_heading("  * list item"), None);
+        assert_eq!(parse_heading("Plain text"), None);
+    }
+
+    #[test]
+    fn parses_closed_and_open_clocks() {
+        assert_eq!(
+            parse_clock(" [2024-05-01 Wed 09:12]--[2024-05-01 Wed 10:30] =>  1:18"),
+            Some((at("2024-05-01", "09:12"), Some(at("2024-05-01", "10:30"))))
+        );
+        assert_eq!(
+            parse_clock(" [2024-05-01 Wed 09:12]"),
+            Some((at("2024-05-01", "09:12"), None))
+        );
+        assert_eq!(parse_clock(" [not a date]"), None);
+    }
+
+    #[test]
+    fn skips_localized_day_names() {
+        assert_eq!(
+            parse_timestamp("2024-05-01 Mi 09:12"),
+            Some(at("2024-05-01", "09:12"))
+        );
+        assert_eq!(
+            parse_timestamp("2024-05-01 mer. 09:12"),
+            Some(at("2024-05-01", "09:12"))
+        );
+        assert_eq!(
+            parse_timestamp("2024-05-01 水"),
+            Some(at("2024-05-01", "00:00"))
+        );
+        assert_eq!(parse_timestamp("05/01/2024 09:12"), None);
+    }
+
+    #[test]
+    fn parses_clock_entries_with_their_heading_and_effort() {
+        let text = "\
+CLOCK: [2024-04-30 Tue 17:00]--[2024-04-30 Tue 18:00] =>  1:00
+* TODO Write report :work:
+:PROPERTIES:
+:Effort:   1:30
+:END:
+:LOGBOOK:
+CLOCK: [2024-05-01 Wed 13:00]
+CLOCK: [2024-05-01 Wed 09:12]--[2024-05-01 Wed 10:30] =>  1:18
+:END:
+*bold* text does not start a heading
+CLOCK: [2024-05-02 Thu 08:00]--[2024-05-02 Thu 08:30] =>  0:30
+** Review pull request
+   CLOCK: [2024-05-02 Thu 11:00]--[2024-05-02 Thu 11:15] =>  0:15
+";
+
+        assert_eq!(
+            parse_clock_entries(text),
+            [
+                ClockEntry {
+                    heading: String::from("Write report"),
+                    start: at("2024-05-01", "13:00"),
+                    end: None,
+                    effort: Some(Duration::minutes(90)),
+                },
+                ClockEntry {
+                    heading: String::from("Write report"),
+                    start: at("2024-05-01", "09:12"),
+                    end: Some(at("2024-05-01", "10:30")),
+                    effort: Some(Duration::minutes(90)),
+                },
+                ClockEntry {
+                    heading: String::from("Write report"),
+                    start: at("2024-05-02", "08:00"),
+                    end: Some(at("2024-05-02", "08:30")),
+                    effort: Some(Duration::minutes(90)),
+                },
+                ClockEntry {
+                    heading: String::from("Review pull request"),
+                    start: at("2024-05-02", "11:00"),
+                    end: Some(at("2024-05-02", "11:15")),
+                    effort: None,
+                },
+            ]
+        );
+    }
+
+    fn entry(heading: &str, start: NaiveDateTime, end: Option<NaiveDateTime>) -> ClockEntry {
+        ClockEntry {
+            heading: heading.to_owned(),
+            start,
+            end,
+            effort: None,
+        }
+    }
+
+    #[test]
+    fn clips_clocked_time_to_the_range() {
+        let entries = [
+            // Started the day before
+            entry(
+                "Deploy",
+                at("2024-04-30", "23:00"),
+                Some(at("2024-05-01", "01:00")),
+            ),
+            // Ends after the range
+            entry(
+                "Write report",
+                at("2024-05-01", "11:00"),
+                Some(at("2024-05-01", "14:00")),
+            ),
+            // Outside of the range altogether
+            entry(
+                "Write report",
+                at("2024-04-30", "09:00"),
+                Some(at("2024-04-30", "17:00")),
+            ),
+            entry(
+                "Review pull request",
+                at("2024-05-01", "09:00"),
+                Some(at("2024-05-01", "10:00")),
+            ),
+        ];
+
+        assert_eq!(
+            clocked_time(
+                &entries,
+                at("2024-05-01", "00:00"),
+                at("2024-05-01", "12:00")
+            ),
+            [
+                (String::from("Deploy"), Duration::hours(1)),
+                (String::from("Review pull request"), Duration::hours(1)),
+                (String::from("Write report"), Duration::hours(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_running_clocks_up_to_the_end_of_the_range() {
+        let entries = [
+            entry("Write report", at("2024-05-01", "09:00"), None),
+            entry(
+                "Write report",
+                at("2024-05-01", "08:00"),
+                Some(at("2024-05-01", "08:30")),
+            ),
+            entry("Deploy", at("2024-05-01", "13:00"), None),
+        ];
+
+        assert_eq!(
+            clocked_time(
+                &entries,
+                at("2024-05-01", "00:00"),
+                at("2024-05-01", "12:00")
+            ),
+            [(String::from("Write report"), Duration::minutes(210))]
+        );
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("1:30"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1:30:00"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("0:00:45"), Some(Duration::seconds(45)));
+        assert_eq!(parse_duration("90min"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1d 2h"), Some(Duration::hours(26)));
+        assert_eq!(parse_duration("45"), Some(Duration::minutes(45)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("2 parsecs"), None);
+        assert_eq!(parse_duration("1:xx"), None);
+        assert_eq!(parse_duration("1:30:00:00"), None);
+    }
+
+    #[test]
+    fn expands_home() {
+        let home = PathBuf::from(std::env::var_os("HOME").unwrap());
+
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/org/work.org"), home.join("org/work.org"));
+        assert_eq!(
+            expand_home("~other/work.org"),
+            PathBuf::from("~other/work.org")
+        );
+        assert_eq!(
+            expand_home("/org/~/work.org"),
+            PathBuf::from("/org/~/work.org")
+        );
+    }
+
+    #[test]
+    fn skips_files_that_cannot_be_read() {
+        let dir =
+            std::env::temp_dir().join(format!("waybar-extensions-org-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("work.org");
+        std::fs::write(
+            &file,
+            "* Write report\n:LOGBOOK:\nCLOCK: [2024-05-01 Wed 09:00]--[2024-05-01 Wed 10:00] =>  1:00\n:END:\n",
+        )
+        .unwrap();
+        let missing = dir.join("missing.org").display().to_string();
+
+        let entries = clock_entries(&[file.display().to_string(), missing.clone()]);
+        let only_missing = clock_entries(&[missing]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let entries = entries.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].heading, "Write report");
+        assert!(only_missing.is_err());
+    }
+}
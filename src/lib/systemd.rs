@@ -0,0 +1,206 @@
+use std::process::Command;
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::OwnedObjectPath;
+
+type BoxedError = Box<dyn std::error::Error>;
+
+const SYSTEMD_NAME: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+
+/// A unit as returned by `ListUnitsFiltered`: name, description, load state, active state, sub
+/// state, followed unit, object path, job id, job type and job path
+type ListedUnit = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    OwnedObjectPath,
+    u32,
+    String,
+    OwnedObjectPath,
+);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Manager {
+    User,
+    System,
+}
+
+impl Manager {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Manager::User => "user",
+            Manager::System => "system",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FailedUnit {
+    pub name: String,
+    pub description: String,
+    pub manager: Manager,
+    /// When the unit entered the failed state, if systemd remembers
+    pub failed_at: Option<DateTime<Local>>,
+}
+
+/// A unit as printed by `systemctl list-units --output=json`
+#[derive(Deserialize, Debug, PartialEq)]
+struct SystemctlUnit {
+    unit: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// Lists the failed units of a service manager, asking systemd over D-Bus and falling back to
+/// `systemctl` when the bus cannot be reached.
+pub fn failed_units(manager: Manager) -> Result<Vec<FailedUnit>, BoxedError> {
+    failed_units_dbus(manager).or_else(|_| failed_units_systemctl(manager))
+}
+
+pub fn failed_units_dbus(manager: Manager) -> Result<Vec<FailedUnit>, BoxedError> {
+    let connection = match manager {
+        Manager::User => Connection::session()?,
+        Manager::System => Connection::system()?,
+    };
+
+    let units: Vec<ListedUnit> = connection
+        .call_method(
+            Some(SYSTEMD_NAME),
+            SYSTEMD_PATH,
+            Some(MANAGER_INTERFACE),
+            "ListUnitsFiltered",
+            &(vec!["failed"],),
+        )?
+        .body()
+        .deserialize()?;
+
+    Ok(units
+        .into_iter()
+        .map(|(name, description, _, _, _, _, path, _, _, _)| {
+            let failed_at = Proxy::new(&connection, SYSTEMD_NAME, path, UNIT_INTERFACE)
+                .and_then(|unit| unit.get_property::<u64>("InactiveEnterTimestamp"))
+                .ok()
+                .and_then(from_micros);
+
+            FailedUnit {
+                name,
+                description,
+                manager,
+                failed_at,
+            }
+        })
+        .collect())
+}
+
+pub fn failed_units_systemctl(manager: Manager) -> Result<Vec<FailedUnit>, BoxedError> {
+    let command_output = systemctl(manager)
+        .args(["list-units", "--failed", "--output=json"])
+        .output()?;
+    if !command_output.status.success() {
+        return Err(String::from_utf8_lossy(&command_output.stderr)
+            .trim()
+            .into());
+    }
+    let units: Vec<SystemctlUnit> = serde_json::from_slice(&command_output.stdout)?;
+
+    Ok(units
+        .into_iter()
+        .map(|unit| FailedUnit {
+            failed_at: failed_at_systemctl(manager, &unit.unit),
+            name: unit.unit,
+            description: unit.description,
+            manager,
+        })
+        .collect())
+}
+
+fn failed_at_systemctl(manager: Manager, unit: &str) -> Option<DateTime<Local>> {
+    let command_output = systemctl(manager)
+        .args([
+            "show",
+            "--property=InactiveEnterTimestamp",
+            "--value",
+            "--timestamp=unix",
+            unit,
+        ])
+        .output()
+        .ok()?;
+
+    parse_unix_timestamp(&String::from_utf8(command_output.stdout).ok()?)
+}
+
+/// Parses a timestamp as printed with `--timestamp=unix`, like `@1714550400`. Empty when the
+/// unit never got there.
+fn parse_unix_timestamp(text: &str) -> Option<DateTime<Local>> {
+    let seconds = text.trim().strip_prefix('@')?.parse().ok()?;
+    DateTime::from_timestamp(seconds, 0).map(Into::into)
+}
+
+fn systemctl(manager: Manager) -> Command {
+    let mut command = Command::new("systemctl");
+    if manager == Manager::User {
+        command.arg("--user");
+    }
+    command
+}
+
+/// Converts a systemd timestamp in microseconds, where zero means it never happened.
+fn from_micros(micros: u64) -> Option<DateTime<Local>> {
+    if micros == 0 {
+        return None;
+    }
+
+    DateTime::from_timestamp_micros(micros as i64).map(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_systemctl_units() {
+        let output = include_bytes!("../../tests/fixtures/systemctl/list-units-failed.json");
+
+        assert_eq!(
+            serde_json::from_slice::<Vec<SystemctlUnit>>(output).unwrap(),
+            [
+                SystemctlUnit {
+                    unit: String::from("backup.service"),
+                    description: String::from("Nightly backup to the NAS"),
+                },
+                // Units without a description still show up
+                SystemctlUnit {
+                    unit: String::from("mnt-nas.mount"),
+                    description: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_unix_timestamps() {
+        let expected = DateTime::from_timestamp(1714550400, 0).map(Into::into);
+
+        assert_eq!(parse_unix_timestamp("@1714550400\n"), expected);
+        assert_eq!(parse_unix_timestamp(""), None);
+        assert_eq!(parse_unix_timestamp("n/a"), None);
+        assert_eq!(parse_unix_timestamp("1714550400"), None);
+    }
+
+    #[test]
+    fn converts_microseconds() {
+        assert_eq!(
+            from_micros(1_714_550_400_500_000),
+            DateTime::from_timestamp_micros(1_714_550_400_500_000).map(Into::into)
+        );
+        assert_eq!(from_micros(0), None);
+    }
+}
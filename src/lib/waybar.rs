@@ -9,17 +9,20 @@ pub struct WaybarResponse {
     pub class: Vec<String>,
 }
 
+/// Lays out rows under bold headings. Cells are plain text, which is escaped for the markup only
+/// after padding, so characters like `&` do not throw off the widths.
 pub fn columnize_output(output: &[Vec<String>], column_heading: &[String]) -> String {
-    let heading_size = column_heading.iter().map(|x| x.len()).collect::<Vec<_>>();
+    let heading_size = column_heading
+        .iter()
+        .map(|x| x.chars().count())
+        .collect::<Vec<_>>();
     let max_size = output
         .iter()
         .fold(heading_size, |mut acc: Vec<usize>, cur| {
             acc.iter_mut()
                 .zip(cur.iter())
                 .for_each(|(acc_val, cur_val)| {
-                    if cur_val.len() > *acc_val {
-                        std::mem::swap(acc_val, &mut cur_val.len());
-                    }
+                    *acc_val = (*acc_val).max(cur_val.chars().count());
                 });
 
             acc
@@ -29,17 +32,67 @@ pub fn columnize_output(output: &[Vec<String>], column_heading: &[String]) -> St
         column_heading
             .iter()
             .enumerate()
-            .map(|(i, val)| format!("<b>{val:width$}</b>", width = max_size[i]))
+            .map(|(i, val)| {
+                format!(
+                    "<b>{}</b>",
+                    escape_markup(&format!("{val:width$}", width = max_size[i]))
+                )
+            })
             .collect::<Vec<_>>()
             .join("    "),
     )
     .chain(output.iter().map(|row| {
         row.iter()
             .enumerate()
-            .map(|(i, val)| format!("{val:width$}", width = max_size[i]))
+            .map(|(i, val)| escape_markup(&format!("{val:width$}", width = max_size[i])))
             .collect::<Vec<_>>()
             .join("    ")
     }))
     .collect::<Vec<_>>()
     .join("\n")
 }
+
+/// Escapes text for use in the Pango markup of tooltips.
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Cuts text to at most `max_length` characters, marking the cut with an ellipsis.
+pub fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_owned();
+    }
+
+    let mut truncated = text
+        .chars()
+        .take(max_length.saturating_sub(1))
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_columns_before_escaping() {
+        let rows = vec![
+            vec![String::from("Q&A <draft>"), String::from("1:00")],
+            vec![String::from("Überblick"), String::from("0:30")],
+        ];
+        let headings = [String::from("Task"), String::from("Today")];
+
+        assert_eq!(
+            columnize_output(&rows, &headings),
+            [
+                "<b>Task       </b>    <b>Today</b>",
+                "Q&amp;A &lt;draft&gt;    1:00 ",
+                "Überblick      0:30 ",
+            ]
+            .join("\n")
+        );
+    }
+}